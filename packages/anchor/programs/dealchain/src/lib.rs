@@ -7,13 +7,26 @@
 // ============================================================================
 
 use anchor_lang::prelude::*;
+use anchor_lang::solana_program::hash::hashv;
+use anchor_lang::solana_program::program::{invoke, invoke_signed};
+use anchor_lang::solana_program::program_option::COption;
+use anchor_lang::system_program::{self, CreateAccount};
 use anchor_spl::{
-    associated_token::AssociatedToken,
-    token::{self, Burn, Mint, Token, TokenAccount, Transfer},
+    associated_token::{self, AssociatedToken},
+    token::{self, Burn, Mint, MintTo, Token, TokenAccount, Transfer},
+    token_2022::{self, Token2022},
 };
+use spl_token_2022::extension::{metadata_pointer, ExtensionType};
+use spl_token_metadata_interface::state::{Field, TokenMetadata};
 
 declare_id!("DChain11111111111111111111111111111111111111");
 
+/// Metaplex Token Metadata program (collection-gated coupons)
+pub mod token_metadata {
+    use super::*;
+    declare_id!("metaqbxxUerdq28cj1RbAWkYQm3ybzjb6a8bt518x1s");
+}
+
 // ============ CONSTANTS ============
 
 const MIN_PRICE: u64 = 1_000; // 0.001 USDC minimum
@@ -22,8 +35,37 @@ const MIN_EXPIRY_DURATION: i64 = 3600; // 1 hour minimum
 const MAX_EXPIRY_DURATION: i64 = 31536000; // 1 year maximum
 const MAX_REDEMPTIONS_PER_COUPON: u32 = 10_000;
 const GRACE_PERIOD: i64 = 86400; // 24h before close
+const MAX_MERCHANT_NAME_LEN: usize = 100;
+const MAX_DESCRIPTION_LEN: usize = 500;
+const MAX_URI_LEN: usize = 200;
+const MAX_WEBSITE_LEN: usize = 200;
+const MAX_VERIFIERS: usize = 10;
+const MAX_MODERATORS: usize = 10;
+const FLAG_WINDOW: i64 = 86400; // Flag rate limit window (1 day)
+const MAX_FLAGS_PER_WINDOW: u16 = 10;
+const MAX_MERCHANT_STAFF: usize = 5;
+const MAX_COMMENT_LEN: usize = 500;
 const MAX_RATING: u8 = 5;
 const MIN_RATING: u8 = 1;
+const BPS_DENOMINATOR: u64 = 10_000;
+const MAX_TIER_DISCOUNT_BPS: u16 = 5_000; // 50% maximum
+const MICRO_UNITS_PER_TOKEN: u128 = 1_000_000; // Point value precision
+const MAX_POINTS_PER_TOKEN: u32 = 1_000;
+const MAX_CATEGORY_MULTIPLIER_BPS: u16 = 50_000; // 5x maximum
+const MIN_DECAY_PERIOD: i64 = 86400; // 1 day minimum
+const MAX_DECAY_STEPS: i64 = 64; // Balances are negligible after this many periods
+const MAX_STAMPS_REQUIRED: u16 = 100;
+const BADGE_SYMBOL: &str = "DCTIER";
+const DEFAULT_BADGE_URI_BASE: &str = "https://dealchain.app/badges";
+const MAX_BADGE_URI_BASE_LEN: usize = 128;
+const BADGE_TIER_KEY: &str = "tier";
+const MAX_ACHIEVEMENTS: u16 = 64; // Claimed flags are a u64 bitmask
+const MAX_ACHIEVEMENT_REWARD_POINTS: u32 = 10_000;
+const MAX_CAMPAIGN_NAME_LEN: usize = 64;
+const MAX_CATEGORY_NAME_LEN: usize = 32;
+const MAX_TRACKED_CATEGORIES: usize = 16; // Distinct categories kept for achievements
+const MIN_CONFIG_CHANGE_DELAY: i64 = 172800; // 48h notice for fee and limit changes
+const METADATA_V1_KEY: u8 = 4; // Metaplex account discriminator for Metadata
 
 // ============ PROGRAM ============
 
@@ -31,6 +73,390 @@ const MIN_RATING: u8 = 1;
 pub mod dealchain {
     use super::*;
 
+    /// Initialize the global config (program upgrade authority only)
+    pub fn initialize_config(
+        ctx: Context<InitializeConfig>,
+        point_value: u64,
+        points_funding: PointsFunding,
+    ) -> Result<()> {
+        require!(point_value > 0, ErrorCode::InvalidPointValue);
+
+        let config = &mut ctx.accounts.config;
+        config.admin = ctx.accounts.admin.key();
+        config.point_value = point_value;
+        config.points_funding = points_funding;
+        config.loyalty_rules = LoyaltyRules::default();
+        config.achievement_count = 0;
+        config.verifiers = Vec::new();
+        config.moderators = Vec::new();
+        config.unverified_max_price = 0;
+        config.paused = PauseFlags::default();
+        config.next_change_id = 0;
+        config.category_count = 0;
+        config.decay_enabled_at = 0;
+        config.badge_uri_base = DEFAULT_BADGE_URI_BASE.to_string();
+        config.badge_uri_version = 0;
+        config.bump = ctx.bumps.config;
+
+        emit!(ConfigInitialized {
+            config: config.key(),
+            admin: config.admin,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Queue a fee or limit change that can execute after a notice period (admin only)
+    pub fn queue_config_change(
+        ctx: Context<QueueConfigChange>,
+        change: ConfigChange,
+        delay: i64,
+    ) -> Result<()> {
+        require!(
+            delay >= MIN_CONFIG_CHANGE_DELAY,
+            ErrorCode::TimelockTooShort
+        );
+        validate_config_change(&change)?;
+
+        let config = &mut ctx.accounts.config;
+        let pending = &mut ctx.accounts.pending_change;
+        let now = Clock::get()?.unix_timestamp;
+
+        pending.id = config.next_change_id;
+        pending.change = change;
+        pending.queued_at = now;
+        pending.eta = now
+            .checked_add(delay)
+            .ok_or(ErrorCode::ArithmeticOverflow)?;
+        pending.bump = ctx.bumps.pending_change;
+
+        config.next_change_id = config
+            .next_change_id
+            .checked_add(1)
+            .ok_or(ErrorCode::ArithmeticOverflow)?;
+
+        emit!(ConfigChangeQueued {
+            id: pending.id,
+            change: pending.change.clone(),
+            eta: pending.eta,
+            timestamp: now,
+        });
+
+        Ok(())
+    }
+
+    /// Apply a queued change once its notice period has passed (admin only)
+    pub fn execute_config_change(ctx: Context<ResolveConfigChange>) -> Result<()> {
+        let pending = &ctx.accounts.pending_change;
+        let now = Clock::get()?.unix_timestamp;
+
+        require!(now >= pending.eta, ErrorCode::TimelockNotElapsed);
+        validate_config_change(&pending.change)?;
+
+        apply_config_change(
+            &mut ctx.accounts.config,
+            ctx.accounts.category.as_mut(),
+            &pending.change,
+            now,
+        )?;
+
+        emit!(ConfigChangeExecuted {
+            id: pending.id,
+            timestamp: now,
+        });
+
+        // Rent returned to admin via close constraint
+        Ok(())
+    }
+
+    /// Drop a queued change before it executes (admin only)
+    pub fn cancel_config_change(ctx: Context<ResolveConfigChange>) -> Result<()> {
+        emit!(ConfigChangeCancelled {
+            id: ctx.accounts.pending_change.id,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        // Rent returned to admin via close constraint
+        Ok(())
+    }
+
+    /// Grant the review moderator role (admin only)
+    pub fn add_moderator(ctx: Context<UpdateConfig>, moderator: Pubkey) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+        require!(
+            !config.moderators.contains(&moderator),
+            ErrorCode::ModeratorAlreadyExists
+        );
+        require!(
+            config.moderators.len() < MAX_MODERATORS,
+            ErrorCode::TooManyModerators
+        );
+
+        config.moderators.push(moderator);
+
+        emit!(ModeratorUpdated {
+            moderator,
+            is_moderator: true,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Revoke the review moderator role (admin only)
+    pub fn remove_moderator(ctx: Context<UpdateConfig>, moderator: Pubkey) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+        let position = config
+            .moderators
+            .iter()
+            .position(|m| *m == moderator)
+            .ok_or(ErrorCode::ModeratorNotFound)?;
+
+        config.moderators.swap_remove(position);
+
+        emit!(ModeratorUpdated {
+            moderator,
+            is_moderator: false,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Grant the merchant verifier role (admin only)
+    pub fn add_verifier(ctx: Context<UpdateConfig>, verifier: Pubkey) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+        require!(
+            !config.verifiers.contains(&verifier),
+            ErrorCode::VerifierAlreadyExists
+        );
+        require!(
+            config.verifiers.len() < MAX_VERIFIERS,
+            ErrorCode::TooManyVerifiers
+        );
+
+        config.verifiers.push(verifier);
+
+        emit!(VerifierUpdated {
+            verifier,
+            is_verifier: true,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Revoke the merchant verifier role (admin only)
+    pub fn remove_verifier(ctx: Context<UpdateConfig>, verifier: Pubkey) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+        let position = config
+            .verifiers
+            .iter()
+            .position(|v| *v == verifier)
+            .ok_or(ErrorCode::VerifierNotFound)?;
+
+        config.verifiers.swap_remove(position);
+
+        emit!(VerifierUpdated {
+            verifier,
+            is_verifier: false,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Set the protocol-wide pause flags, replacing all of them (admin only)
+    pub fn set_pause_flags(ctx: Context<UpdateConfig>, paused: PauseFlags) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+        config.paused = paused.clone();
+
+        emit!(PauseFlagsUpdated {
+            paused,
+            admin: ctx.accounts.admin.key(),
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Set where badge NFT metadata JSON is hosted (admin only)
+    pub fn set_badge_uri_base(ctx: Context<UpdateConfig>, badge_uri_base: String) -> Result<()> {
+        require!(!badge_uri_base.is_empty(), ErrorCode::UriEmpty);
+        require!(
+            badge_uri_base.len() <= MAX_BADGE_URI_BASE_LEN,
+            ErrorCode::UriTooLong
+        );
+        require!(is_valid_uri(&badge_uri_base), ErrorCode::InvalidUri);
+
+        let config = &mut ctx.accounts.config;
+        config.badge_uri_base = badge_uri_base.clone();
+        config.badge_uri_version = config
+            .badge_uri_version
+            .checked_add(1)
+            .ok_or(ErrorCode::ArithmeticOverflow)?;
+
+        emit!(BadgeUriBaseUpdated {
+            badge_uri_base,
+            admin: ctx.accounts.admin.key(),
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Mark a merchant verified, recording the KYC/KYB evidence hash (verifier only)
+    pub fn verify_merchant(
+        ctx: Context<SetMerchantVerification>,
+        evidence_hash: [u8; 32],
+    ) -> Result<()> {
+        set_merchant_verification(ctx, true, evidence_hash)
+    }
+
+    /// Withdraw a merchant's verification, recording the reason's hash (verifier only)
+    pub fn revoke_verification(
+        ctx: Context<SetMerchantVerification>,
+        evidence_hash: [u8; 32],
+    ) -> Result<()> {
+        set_merchant_verification(ctx, false, evidence_hash)
+    }
+
+    /// Register a coupon category, optionally nested under a parent (admin only)
+    pub fn create_category(
+        ctx: Context<CreateCategory>,
+        name: String,
+        icon_uri: String,
+        points_multiplier_bps: u16,
+    ) -> Result<()> {
+        validate_category(&name, &icon_uri)?;
+        require!(
+            points_multiplier_bps <= MAX_CATEGORY_MULTIPLIER_BPS,
+            ErrorCode::InvalidLoyaltyRules
+        );
+
+        let config = &mut ctx.accounts.config;
+        let category = &mut ctx.accounts.category;
+
+        category.id = config.category_count;
+        category.name = name;
+        category.parent = ctx.accounts.parent.as_ref().map(|parent| parent.key());
+        category.icon_uri = icon_uri;
+        category.points_multiplier_bps = points_multiplier_bps;
+        category.is_active = true;
+        category.coupon_count = 0;
+        category.purchase_count = 0;
+        category.redemption_count = 0;
+        category.created_at = Clock::get()?.unix_timestamp;
+        category.bump = ctx.bumps.category;
+
+        config.category_count = config
+            .category_count
+            .checked_add(1)
+            .ok_or(ErrorCode::ArithmeticOverflow)?;
+
+        emit!(CategoryCreated {
+            category: category.key(),
+            id: category.id,
+            name: category.name.clone(),
+            parent: category.parent,
+            points_multiplier_bps,
+            timestamp: category.created_at,
+        });
+
+        Ok(())
+    }
+
+    /// Rename, re-icon or retire a category (admin only). Multipliers change
+    /// through the config timelock.
+    pub fn update_category(
+        ctx: Context<UpdateCategory>,
+        name: String,
+        icon_uri: String,
+        is_active: bool,
+    ) -> Result<()> {
+        validate_category(&name, &icon_uri)?;
+
+        let category = &mut ctx.accounts.category;
+        category.name = name;
+        category.icon_uri = icon_uri;
+        category.is_active = is_active;
+
+        emit!(CategoryUpdated {
+            category: category.key(),
+            name: category.name.clone(),
+            icon_uri: category.icon_uri.clone(),
+            is_active,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Define a new achievement (admin only)
+    pub fn create_achievement(
+        ctx: Context<CreateAchievement>,
+        kind: AchievementKind,
+        target: u32,
+        reward_points: u32,
+    ) -> Result<()> {
+        require!(target > 0, ErrorCode::InvalidAchievement);
+        require!(
+            reward_points <= MAX_ACHIEVEMENT_REWARD_POINTS,
+            ErrorCode::InvalidAchievement
+        );
+        if kind == AchievementKind::CategoriesRedeemed {
+            require!(
+                target as usize <= MAX_TRACKED_CATEGORIES,
+                ErrorCode::InvalidAchievement
+            );
+        }
+
+        let config = &mut ctx.accounts.config;
+        let achievement = &mut ctx.accounts.achievement;
+
+        achievement.id = config.achievement_count;
+        achievement.kind = kind;
+        achievement.target = target;
+        achievement.reward_points = reward_points;
+        achievement.is_active = true;
+        achievement.bump = ctx.bumps.achievement;
+
+        config.achievement_count = config
+            .achievement_count
+            .checked_add(1)
+            .ok_or(ErrorCode::ArithmeticOverflow)?;
+
+        emit!(AchievementCreated {
+            achievement: achievement.key(),
+            id: achievement.id,
+            kind: achievement.kind.clone(),
+            target,
+            reward_points,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Enable or retire an achievement (admin only)
+    pub fn set_achievement_active(
+        ctx: Context<SetAchievementActive>,
+        is_active: bool,
+    ) -> Result<()> {
+        let achievement = &mut ctx.accounts.achievement;
+        achievement.is_active = is_active;
+
+        emit!(AchievementStatusUpdated {
+            achievement: achievement.key(),
+            id: achievement.id,
+            is_active,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
     /// Initialize a merchant account with strict validation
     pub fn initialize_merchant(
         ctx: Context<InitializeMerchant>,
@@ -38,23 +464,32 @@ pub mod dealchain {
         description: String,
     ) -> Result<()> {
         // Input validation
-        require!(name.len() > 0, ErrorCode::NameEmpty);
-        require!(name.len() <= 100, ErrorCode::NameTooLong);
-        require!(description.len() <= 500, ErrorCode::DescriptionTooLong);
-        require!(is_valid_utf8(&name), ErrorCode::InvalidUtf8);
-        require!(is_valid_utf8(&description), ErrorCode::InvalidUtf8);
+        validate_merchant_profile(&name, &description, "", "")?;
 
         let merchant = &mut ctx.accounts.merchant;
         merchant.authority = ctx.accounts.authority.key();
+        merchant.merchant_id = ctx.accounts.authority.key();
+        merchant.pending_authority = None;
         merchant.name = name;
         merchant.description = description;
+        merchant.logo_uri = String::new();
+        merchant.website = String::new();
         merchant.total_coupons_created = 0;
+        merchant.total_campaigns = 0;
         merchant.total_redemptions = 0;
         merchant.total_revenue = 0;
         merchant.rating_sum = 0;
         merchant.rating_count = 0;
         merchant.is_verified = false;
+        merchant.verification_evidence = [0; 32];
+        merchant.verified_by = Pubkey::default();
+        merchant.verification_updated_at = 0;
         merchant.is_paused = false;
+        merchant.is_closing = false;
+        merchant.open_coupons = 0;
+        merchant.is_closed = false;
+        merchant.tier_discount_bps = [0; 4];
+        merchant.staff = Vec::new();
         merchant.created_at = Clock::get()?.unix_timestamp;
         merchant.bump = ctx.bumps.merchant;
 
@@ -67,7 +502,155 @@ pub mod dealchain {
         Ok(())
     }
 
+    /// Update merchant profile, resizing the account to fit (merchant only)
+    pub fn update_merchant_profile(
+        ctx: Context<UpdateMerchantProfile>,
+        name: String,
+        description: String,
+        logo_uri: String,
+        website: String,
+    ) -> Result<()> {
+        validate_merchant_profile(&name, &description, &logo_uri, &website)?;
+
+        let merchant = &mut ctx.accounts.merchant;
+        merchant.name = name;
+        merchant.description = description;
+        merchant.logo_uri = logo_uri;
+        merchant.website = website;
+
+        emit!(MerchantUpdated {
+            merchant: merchant.key(),
+            name: merchant.name.clone(),
+            description: merchant.description.clone(),
+            logo_uri: merchant.logo_uri.clone(),
+            website: merchant.website.clone(),
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Nominate a new merchant authority (current authority only)
+    pub fn propose_authority_transfer(
+        ctx: Context<ProposeAuthorityTransfer>,
+        new_authority: Pubkey,
+    ) -> Result<()> {
+        let merchant = &mut ctx.accounts.merchant;
+        require!(
+            new_authority != merchant.authority,
+            ErrorCode::InvalidNewAuthority
+        );
+
+        merchant.pending_authority = Some(new_authority);
+
+        emit!(MerchantAuthorityTransferProposed {
+            merchant: merchant.key(),
+            authority: merchant.authority,
+            pending_authority: new_authority,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Withdraw a pending authority nomination (current authority only)
+    pub fn cancel_authority_transfer(ctx: Context<ProposeAuthorityTransfer>) -> Result<()> {
+        let merchant = &mut ctx.accounts.merchant;
+        require!(
+            merchant.pending_authority.is_some(),
+            ErrorCode::NoPendingAuthority
+        );
+
+        merchant.pending_authority = None;
+
+        emit!(MerchantAuthorityTransferCancelled {
+            merchant: merchant.key(),
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Complete a transfer; must be signed by the nominated authority
+    pub fn accept_authority_transfer(ctx: Context<AcceptAuthorityTransfer>) -> Result<()> {
+        let merchant = &mut ctx.accounts.merchant;
+        let previous_authority = merchant.authority;
+
+        merchant.authority = ctx.accounts.new_authority.key();
+        merchant.pending_authority = None;
+
+        emit!(MerchantAuthorityTransferred {
+            merchant: merchant.key(),
+            previous_authority,
+            new_authority: merchant.authority,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Create a campaign grouping coupons under a shared discount budget
+    pub fn create_campaign(
+        ctx: Context<CreateCampaign>,
+        name: String,
+        start_timestamp: i64,
+        end_timestamp: i64,
+        budget: u64,
+    ) -> Result<()> {
+        let merchant = &mut ctx.accounts.merchant;
+        let clock = Clock::get()?;
+
+        require!(!merchant.is_paused, ErrorCode::MerchantPaused);
+        require!(!merchant.is_closing, ErrorCode::MerchantClosing);
+        require!(!name.is_empty(), ErrorCode::NameEmpty);
+        require!(name.len() <= MAX_CAMPAIGN_NAME_LEN, ErrorCode::NameTooLong);
+        require!(is_valid_utf8(&name), ErrorCode::InvalidUtf8);
+        require!(
+            start_timestamp < end_timestamp && end_timestamp > clock.unix_timestamp,
+            ErrorCode::InvalidCampaignWindow
+        );
+        require!(
+            end_timestamp < clock.unix_timestamp + MAX_EXPIRY_DURATION,
+            ErrorCode::ExpiryTooFar
+        );
+        require!(budget > 0, ErrorCode::InvalidCampaignBudget);
+
+        let campaign = &mut ctx.accounts.campaign;
+        campaign.merchant = merchant.key();
+        campaign.id = merchant.total_campaigns;
+        campaign.name = name;
+        campaign.start_timestamp = start_timestamp;
+        campaign.end_timestamp = end_timestamp;
+        campaign.budget = budget;
+        campaign.budget_committed = 0;
+        campaign.total_coupons = 0;
+        campaign.total_purchases = 0;
+        campaign.total_redemptions = 0;
+        campaign.total_revenue = 0;
+        campaign.created_at = clock.unix_timestamp;
+        campaign.bump = ctx.bumps.campaign;
+
+        merchant.total_campaigns = merchant
+            .total_campaigns
+            .checked_add(1)
+            .ok_or(ErrorCode::ArithmeticOverflow)?;
+
+        emit!(CampaignCreated {
+            campaign: campaign.key(),
+            merchant: campaign.merchant,
+            id: campaign.id,
+            name: campaign.name.clone(),
+            start_timestamp,
+            end_timestamp,
+            budget,
+            timestamp: campaign.created_at,
+        });
+
+        Ok(())
+    }
+
     /// Create a new coupon with comprehensive security checks
+    #[allow(clippy::too_many_arguments)]
     pub fn create_coupon(
         ctx: Context<CreateCoupon>,
         discount_percent: u8,
@@ -75,24 +658,30 @@ pub mod dealchain {
         price: u64,
         expiry_timestamp: i64,
         max_redemptions: u32,
-        category: CouponCategory,
         is_transferable: bool,
         metadata_uri: String,
+        gate: CouponGate,
+        max_points_per_purchase: u32,
     ) -> Result<()> {
         let merchant = &mut ctx.accounts.merchant;
         let clock = Clock::get()?;
 
         // Security checks
         require!(!merchant.is_paused, ErrorCode::MerchantPaused);
+        require!(!merchant.is_closing, ErrorCode::MerchantClosing);
 
         // Discount validation
         require!(discount_percent <= 100, ErrorCode::InvalidDiscount);
 
         // Price validation with bounds
         require!(
-            price >= MIN_PRICE && price <= MAX_PRICE,
+            (MIN_PRICE..=MAX_PRICE).contains(&price),
             ErrorCode::InvalidPrice
         );
+        require!(
+            is_price_allowed(merchant, &ctx.accounts.config, price),
+            ErrorCode::PriceExceedsUnverifiedLimit
+        );
 
         // Expiry validation with reasonable bounds
         require!(
@@ -111,42 +700,95 @@ pub mod dealchain {
         );
 
         // URI validation
-        require!(metadata_uri.len() > 0, ErrorCode::UriEmpty);
-        require!(metadata_uri.len() <= 200, ErrorCode::UriTooLong);
-        require!(is_valid_uri(&metadata_uri), ErrorCode::InvalidUri);
+        validate_metadata_uri(&metadata_uri)?;
+
+        // Gate validation
+        require!(is_valid_gate(&gate), ErrorCode::InvalidGate);
+
+        // Campaign coupons reserve their worst-case discount against the budget
+        let campaign_key = match ctx.accounts.campaign.as_mut() {
+            Some(campaign) => {
+                require!(
+                    clock.unix_timestamp < campaign.end_timestamp,
+                    ErrorCode::CampaignEnded
+                );
+                reserve_campaign_budget(
+                    campaign,
+                    0,
+                    committed_discount(price, discount_percent, discount_fixed, max_redemptions)?,
+                )?;
+                campaign.total_coupons = campaign
+                    .total_coupons
+                    .checked_add(1)
+                    .ok_or(ErrorCode::ArithmeticOverflow)?;
+                Some(campaign.key())
+            }
+            None => None,
+        };
 
         let coupon = &mut ctx.accounts.coupon;
 
         coupon.mint = ctx.accounts.mint.key();
         coupon.merchant = merchant.key();
+        coupon.campaign = campaign_key;
+        coupon.index = merchant.total_coupons_created;
         coupon.discount_percent = discount_percent;
         coupon.discount_fixed = discount_fixed;
         coupon.price = price;
+        coupon.payment_mint = ctx.accounts.payment_mint.key();
         coupon.expiry_timestamp = expiry_timestamp;
         coupon.max_redemptions = max_redemptions;
         coupon.current_redemptions = 0;
-        coupon.category = category;
+        coupon.category = ctx.accounts.category.key();
         coupon.is_transferable = is_transferable;
         coupon.is_active = true;
         coupon.metadata_uri = metadata_uri;
         coupon.created_at = clock.unix_timestamp;
         coupon.total_purchases = 0;
+        coupon.paid_total = 0;
+        coupon.holder_reviews = 0;
+        coupon.gate = gate;
+        coupon.max_points_per_purchase = max_points_per_purchase;
+        coupon.rating_sum = 0;
+        coupon.rating_count = 0;
+        coupon.rating_histogram = [0; 5];
+        coupon.is_closed = false;
+        coupon.refund_per_unit = 0;
+        coupon.refunds_remaining = 0;
         coupon.bump = ctx.bumps.coupon;
 
+        let index_entry = &mut ctx.accounts.coupon_index;
+        index_entry.merchant = merchant.key();
+        index_entry.index = coupon.index;
+        index_entry.coupon = coupon.key();
+        index_entry.bump = ctx.bumps.coupon_index;
+
         // Safe increment with overflow protection
         merchant.total_coupons_created = merchant
             .total_coupons_created
             .checked_add(1)
             .ok_or(ErrorCode::ArithmeticOverflow)?;
+        merchant.open_coupons = merchant
+            .open_coupons
+            .checked_add(1)
+            .ok_or(ErrorCode::ArithmeticOverflow)?;
+
+        let category = &mut ctx.accounts.category;
+        category.coupon_count = category
+            .coupon_count
+            .checked_add(1)
+            .ok_or(ErrorCode::ArithmeticOverflow)?;
 
         emit!(CouponCreated {
             coupon: coupon.key(),
             mint: coupon.mint,
             merchant: coupon.merchant,
+            campaign: coupon.campaign,
+            index: coupon.index,
             discount_percent: coupon.discount_percent,
             price: coupon.price,
             max_redemptions: coupon.max_redemptions,
-            category: coupon.category.clone(),
+            category: coupon.category,
             timestamp: coupon.created_at,
         });
 
@@ -154,15 +796,22 @@ pub mod dealchain {
     }
 
     /// Purchase a coupon - SECURED with CEI pattern and atomic state updates
-    pub fn purchase_coupon(ctx: Context<PurchaseCoupon>) -> Result<()> {
+    pub fn purchase_coupon(ctx: Context<PurchaseCoupon>, points_to_spend: u32) -> Result<()> {
         let coupon = &mut ctx.accounts.coupon;
         let merchant = &mut ctx.accounts.merchant;
+        let config = &ctx.accounts.config;
         let clock = Clock::get()?;
 
+        // Bring the badge up to date so gates and discounts see the current tier
+        if let Some(badge) = ctx.accounts.buyer_loyalty_badge.as_mut() {
+            refresh_badge(badge, config, clock.unix_timestamp)?;
+        }
+
         // ===== CHECKS (All validations before any state change) =====
 
         require!(coupon.is_active, ErrorCode::CouponInactive);
         require!(!merchant.is_paused, ErrorCode::MerchantPaused);
+        require!(!merchant.is_closing, ErrorCode::MerchantClosing);
         require!(
             clock.unix_timestamp < coupon.expiry_timestamp,
             ErrorCode::CouponExpired
@@ -172,39 +821,242 @@ pub mod dealchain {
             ErrorCode::SoldOut
         );
 
+        // Campaign coupons only sell inside the campaign window
+        let campaign = coupon_campaign(coupon, &mut ctx.accounts.campaign)?;
+        if let Some(campaign) = campaign.as_ref() {
+            require!(
+                clock.unix_timestamp >= campaign.start_timestamp
+                    && clock.unix_timestamp < campaign.end_timestamp,
+                ErrorCode::CampaignNotActive
+            );
+        }
+
+        // Loyalty tier gate (badge PDA is verified by seeds)
+        if let Some(min_tier) = &coupon.gate.min_tier {
+            let badge = ctx
+                .accounts
+                .buyer_loyalty_badge
+                .as_ref()
+                .ok_or(ErrorCode::LoyaltyBadgeRequired)?;
+            require!(badge.tier >= *min_tier, ErrorCode::LoyaltyTierTooLow);
+        }
+
+        // Token gate (account owner is verified by constraint)
+        if let Some(required_mint) = coupon.gate.required_mint {
+            let gate_account = ctx
+                .accounts
+                .buyer_gate_token_account
+                .as_ref()
+                .ok_or(ErrorCode::GateTokenAccountRequired)?;
+            require!(gate_account.mint == required_mint, ErrorCode::WrongGateMint);
+            require!(
+                gate_account.amount >= coupon.gate.required_amount,
+                ErrorCode::InsufficientGateBalance
+            );
+        }
+
+        // Collection gate (NFT owner is verified by constraint, metadata here)
+        if let Some(collection) = coupon.gate.required_collection {
+            let nft_account = ctx
+                .accounts
+                .buyer_collection_nft_account
+                .as_ref()
+                .ok_or(ErrorCode::CollectionNftRequired)?;
+            let metadata = ctx
+                .accounts
+                .buyer_collection_nft_metadata
+                .as_ref()
+                .ok_or(ErrorCode::CollectionNftRequired)?;
+            require!(nft_account.amount > 0, ErrorCode::NotInCollection);
+            require!(
+                is_collection_member(metadata, &nft_account.mint, &collection)?,
+                ErrorCode::NotInCollection
+            );
+        }
+
+        // Tier discount is based on the tier held before this purchase
+        let list_price = coupon.price; // Cache value
+        let tier = ctx
+            .accounts
+            .buyer_loyalty_badge
+            .as_ref()
+            .map(|badge| badge.tier.clone());
+        let tier_discount = match &tier {
+            Some(tier) => apply_bps(list_price, merchant.tier_discount_bps[tier.index()])?,
+            None => 0,
+        };
+        let discounted_price = list_price
+            .checked_sub(tier_discount)
+            .ok_or(ErrorCode::ArithmeticOverflow)?;
+
+        // A merchant buying its own coupon neither spends nor earns points,
+        // so it can't cycle them into treasury subsidies
+        let self_purchase = ctx.accounts.buyer.key() == merchant.authority;
+
+        // Loyalty points burned toward the remaining price
+        require!(
+            points_to_spend <= coupon.max_points_per_purchase,
+            ErrorCode::PointsCapExceeded
+        );
+        require!(
+            points_to_spend == 0 || !self_purchase,
+            ErrorCode::SelfPurchasePoints
+        );
+        let points_discount = if points_to_spend > 0 {
+            let badge = ctx
+                .accounts
+                .buyer_loyalty_badge
+                .as_ref()
+                .ok_or(ErrorCode::LoyaltyBadgeRequired)?;
+            require!(
+                badge.points >= points_to_spend,
+                ErrorCode::InsufficientPoints
+            );
+            points_to_base_units(
+                points_to_spend,
+                config.point_value,
+                ctx.accounts.mint.decimals,
+            )?
+        } else {
+            0
+        };
+        require!(
+            points_discount <= discounted_price,
+            ErrorCode::PointsExceedPrice
+        );
+        let price = discounted_price - points_discount;
+
+        // Treasury-funded points are paid by the platform, to verified merchants only
+        let treasury_subsidy = match config.points_funding {
+            PointsFunding::Treasury if merchant.is_verified => points_discount,
+            PointsFunding::Treasury | PointsFunding::Merchant => 0,
+        };
+        if treasury_subsidy > 0 {
+            let treasury = ctx
+                .accounts
+                .treasury_token_account
+                .as_ref()
+                .ok_or(ErrorCode::TreasuryAccountRequired)?;
+            require!(
+                treasury.amount >= treasury_subsidy,
+                ErrorCode::InsufficientTreasuryFunds
+            );
+        }
+
         // Verify buyer has enough tokens
         require!(
-            ctx.accounts.buyer_token_account.amount >= coupon.price,
+            ctx.accounts.buyer_token_account.amount >= price,
             ErrorCode::InsufficientFunds
         );
 
         // ===== EFFECTS (Update ALL state BEFORE external calls) =====
 
-        let price = coupon.price; // Cache value
-
         // Atomic state updates with overflow protection
         coupon.total_purchases = coupon
             .total_purchases
             .checked_add(1)
             .ok_or(ErrorCode::ArithmeticOverflow)?;
+        coupon.paid_total = coupon
+            .paid_total
+            .checked_add(price)
+            .ok_or(ErrorCode::ArithmeticOverflow)?;
 
         merchant.total_revenue = merchant
             .total_revenue
             .checked_add(price)
+            .and_then(|revenue| revenue.checked_add(treasury_subsidy))
             .ok_or(ErrorCode::ArithmeticOverflow)?;
 
-        // ===== INTERACTIONS (External calls LAST - reentrancy safe) =====
+        let category = &mut ctx.accounts.category;
+        category.purchase_count = category
+            .purchase_count
+            .checked_add(1)
+            .ok_or(ErrorCode::ArithmeticOverflow)?;
 
-        let cpi_accounts = Transfer {
-            from: ctx.accounts.buyer_token_account.to_account_info(),
-            to: ctx.accounts.merchant_token_account.to_account_info(),
-            authority: ctx.accounts.buyer.to_account_info(),
-        };
-        let cpi_program = ctx.accounts.token_program.to_account_info();
-        let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
+        if let Some(campaign) = campaign {
+            // Tier and merchant-funded points discounts are spent at sale time;
+            // treasury-funded points are paid by the platform, not the merchant
+            let sale_discount = tier_discount
+                .checked_add(points_discount - treasury_subsidy)
+                .ok_or(ErrorCode::ArithmeticOverflow)?;
+            charge_campaign_budget(campaign, sale_discount)?;
+
+            campaign.total_purchases = campaign
+                .total_purchases
+                .checked_add(1)
+                .ok_or(ErrorCode::ArithmeticOverflow)?;
+            campaign.total_revenue = campaign
+                .total_revenue
+                .checked_add(price)
+                .and_then(|revenue| revenue.checked_add(treasury_subsidy))
+                .ok_or(ErrorCode::ArithmeticOverflow)?;
+        }
+
+        // Burn spent points and credit the purchase to the buyer's loyalty badge
+        if let Some(badge) = ctx.accounts.buyer_loyalty_badge.as_mut() {
+            badge.points = badge
+                .points
+                .checked_sub(points_to_spend)
+                .ok_or(ErrorCode::InsufficientPoints)?;
+
+            if !self_purchase {
+                let savings = coupon
+                    .discount_fixed
+                    .checked_add(tier_discount)
+                    .and_then(|savings| savings.checked_add(points_discount))
+                    .ok_or(ErrorCode::ArithmeticOverflow)?;
+                accrue_loyalty(
+                    badge,
+                    &config.loyalty_rules,
+                    ctx.accounts.category.points_multiplier_bps,
+                    price,
+                    ctx.accounts.mint.decimals,
+                    savings,
+                )?;
+            }
+
+            emit!(LoyaltyBadgeUpdated {
+                badge: badge.key(),
+                user: badge.user,
+                tier: badge.tier.clone(),
+                points: badge.points,
+            });
+        }
+
+        // ===== INTERACTIONS (External calls LAST - reentrancy safe) =====
 
-        // Execute transfer (this is the only external call)
-        token::transfer(cpi_ctx, price)?;
+        if price > 0 {
+            let cpi_accounts = Transfer {
+                from: ctx.accounts.buyer_token_account.to_account_info(),
+                to: ctx.accounts.merchant_token_account.to_account_info(),
+                authority: ctx.accounts.buyer.to_account_info(),
+            };
+            let cpi_program = ctx.accounts.token_program.to_account_info();
+            let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
+
+            token::transfer(cpi_ctx, price)?;
+        }
+
+        // Treasury pays its share of the points discount (signed by config PDA)
+        if let Some(treasury) = ctx.accounts.treasury_token_account.as_ref() {
+            if treasury_subsidy > 0 {
+                let config_seeds: &[&[u8]] = &[b"config", &[config.bump]];
+                let signer_seeds = &[config_seeds];
+                let cpi_accounts = Transfer {
+                    from: treasury.to_account_info(),
+                    to: ctx.accounts.merchant_token_account.to_account_info(),
+                    authority: config.to_account_info(),
+                };
+                token::transfer(
+                    CpiContext::new_with_signer(
+                        ctx.accounts.token_program.to_account_info(),
+                        cpi_accounts,
+                        signer_seeds,
+                    ),
+                    treasury_subsidy,
+                )?;
+            }
+        }
 
         // TODO: Mint NFT to buyer using Metaplex
         // This should be done via CPI to Metaplex Token Metadata program
@@ -213,7 +1065,12 @@ pub mod dealchain {
         emit!(CouponPurchased {
             coupon: coupon.key(),
             buyer: ctx.accounts.buyer.key(),
+            list_price,
             price,
+            tier,
+            tier_discount,
+            points_spent: points_to_spend,
+            points_discount,
             timestamp: clock.unix_timestamp,
         });
 
@@ -262,6 +1119,13 @@ pub mod dealchain {
             .checked_add(1)
             .ok_or(ErrorCode::ArithmeticOverflow)?;
 
+        if let Some(campaign) = coupon_campaign(coupon, &mut ctx.accounts.campaign)? {
+            campaign.total_redemptions = campaign
+                .total_redemptions
+                .checked_add(1)
+                .ok_or(ErrorCode::ArithmeticOverflow)?;
+        }
+
         // Create redemption record to prevent duplicate redemptions
         // This PDA can only be created once per user/coupon pair
         redemption.coupon = coupon.key();
@@ -269,6 +1133,65 @@ pub mod dealchain {
         redemption.timestamp = clock.unix_timestamp;
         redemption.bump = ctx.bumps.redemption_record;
 
+        // A review written while holding the unit now rests on this redemption
+        let review_info = ctx.accounts.review.to_account_info();
+        if review_info.owner == &crate::ID && !review_info.data_is_empty() {
+            let mut data = review_info.try_borrow_mut_data()?;
+            let mut review = Review::try_deserialize(&mut &data[..])?;
+            release_holder_review(&mut review.proof, &mut coupon.holder_reviews);
+            review.try_serialize(&mut &mut data[..])?;
+        }
+
+        // Achievement progress
+        if let Some(achievements) = ctx.accounts.achievements.as_mut() {
+            achievements.redemptions = achievements
+                .redemptions
+                .checked_add(1)
+                .ok_or(ErrorCode::ArithmeticOverflow)?;
+            let category_id = ctx.accounts.category.id;
+            if !achievements.categories_redeemed.contains(&category_id)
+                && achievements.categories_redeemed.len() < MAX_TRACKED_CATEGORIES
+            {
+                achievements.categories_redeemed.push(category_id);
+            }
+        }
+
+        let category = &mut ctx.accounts.category;
+        category.redemption_count = category
+            .redemption_count
+            .checked_add(1)
+            .ok_or(ErrorCode::ArithmeticOverflow)?;
+
+        // Stamp the customer's card if the merchant runs a stamp program
+        if let (Some(program), Some(card)) = (
+            ctx.accounts.loyalty_program.as_ref(),
+            ctx.accounts.stamp_card.as_mut(),
+        ) {
+            if program.is_active {
+                card.stamps = card
+                    .stamps
+                    .checked_add(1)
+                    .ok_or(ErrorCode::ArithmeticOverflow)?;
+
+                if card.stamps >= program.stamps_required {
+                    card.stamps -= program.stamps_required;
+                    card.rewards_available = card
+                        .rewards_available
+                        .checked_add(1)
+                        .ok_or(ErrorCode::ArithmeticOverflow)?;
+                }
+
+                emit!(StampAdded {
+                    stamp_card: card.key(),
+                    merchant: merchant.key(),
+                    customer: card.customer,
+                    stamps: card.stamps,
+                    rewards_available: card.rewards_available,
+                    timestamp: clock.unix_timestamp,
+                });
+            }
+        }
+
         // SECURITY: Burn NFT after redemption to prevent reuse
         let burn_accounts = Burn {
             mint: ctx.accounts.mint.to_account_info(),
@@ -294,44 +1217,70 @@ pub mod dealchain {
     pub fn submit_review(ctx: Context<SubmitReview>, rating: u8, comment: String) -> Result<()> {
         // Validate rating
         require!(
-            rating >= MIN_RATING && rating <= MAX_RATING,
+            (MIN_RATING..=MAX_RATING).contains(&rating),
             ErrorCode::InvalidRating
         );
 
         // Validate comment
-        require!(comment.len() <= 500, ErrorCode::CommentTooLong);
-        require!(is_valid_utf8(&comment), ErrorCode::InvalidUtf8);
+        validate_comment(&comment)?;
 
         // CRITICAL: Verify user actually purchased/redeemed the coupon
         // User must own the NFT OR have a redemption record
-        let owns_nft = ctx.accounts.user_nft_account.amount >= 1
-            && ctx.accounts.user_nft_account.mint == ctx.accounts.coupon.mint;
-
-        let has_redeemed = ctx.accounts.redemption_record.user == ctx.accounts.user.key()
-            && ctx.accounts.redemption_record.coupon == ctx.accounts.coupon.key();
-
-        require!(owns_nft || has_redeemed, ErrorCode::MustOwnCouponToReview);
+        let proof = review_proof(
+            &ctx.accounts.coupon.key(),
+            &ctx.accounts.coupon.mint,
+            &ctx.accounts.user.key(),
+            ctx.accounts.user_nft_account.as_deref().map(|a| &**a),
+            ctx.accounts.redemption_record.as_deref(),
+        )
+        .ok_or(ErrorCode::MustOwnCouponToReview)?;
 
         let review = &mut ctx.accounts.review;
         let merchant = &mut ctx.accounts.merchant;
+        let coupon = &mut ctx.accounts.coupon;
 
-        review.coupon = ctx.accounts.coupon.key();
+        // Units share one mint and can be passed between wallets, so each
+        // unredeemed unit backs at most one holder review
+        if proof == ReviewProof::HeldUnit {
+            require!(
+                has_holder_review_slot(
+                    coupon.holder_reviews,
+                    coupon.total_purchases,
+                    coupon.current_redemptions,
+                ),
+                ErrorCode::NoHolderReviewSlot
+            );
+            coupon.holder_reviews = coupon
+                .holder_reviews
+                .checked_add(1)
+                .ok_or(ErrorCode::ArithmeticOverflow)?;
+        }
+
+        review.coupon = coupon.key();
         review.user = ctx.accounts.user.key();
         review.rating = rating;
         review.comment = comment;
         review.timestamp = Clock::get()?.unix_timestamp;
+        review.updated_at = review.timestamp;
+        review.edit_count = 0;
+        review.history_hash = [0; 32];
+        review.is_hidden = false;
+        review.flag_count = 0;
+        review.counted_for_achievements = ctx.accounts.achievements.is_some();
+        review.has_reply = false;
+        review.proof = proof;
         review.bump = ctx.bumps.review;
 
-        // Update merchant rating with overflow protection
-        merchant.rating_sum = merchant
-            .rating_sum
-            .checked_add(rating as u64)
-            .ok_or(ErrorCode::ArithmeticOverflow)?;
+        // Update merchant and coupon ratings with overflow protection
+        add_rating(merchant, coupon, rating)?;
 
-        merchant.rating_count = merchant
-            .rating_count
-            .checked_add(1)
-            .ok_or(ErrorCode::ArithmeticOverflow)?;
+        // Achievement progress
+        if let Some(achievements) = ctx.accounts.achievements.as_mut() {
+            achievements.reviews = achievements
+                .reviews
+                .checked_add(1)
+                .ok_or(ErrorCode::ArithmeticOverflow)?;
+        }
 
         emit!(ReviewSubmitted {
             review: review.key(),
@@ -344,6 +1293,429 @@ pub mod dealchain {
         Ok(())
     }
 
+    /// Rewrite a review, chaining the previous version into its history hash
+    pub fn edit_review(ctx: Context<EditReview>, rating: u8, comment: String) -> Result<()> {
+        require!(
+            (MIN_RATING..=MAX_RATING).contains(&rating),
+            ErrorCode::InvalidRating
+        );
+        validate_comment(&comment)?;
+
+        let review = &mut ctx.accounts.review;
+        let merchant = &mut ctx.accounts.merchant;
+        let coupon = &mut ctx.accounts.coupon;
+        let now = Clock::get()?.unix_timestamp;
+        let old_rating = review.rating;
+
+        // Swap the old rating for the new one (hidden reviews are not counted)
+        if !review.is_hidden {
+            remove_rating(merchant, coupon, old_rating)?;
+            add_rating(merchant, coupon, rating)?;
+        }
+
+        review.history_hash = review_history_hash(review);
+        review.edit_count = review
+            .edit_count
+            .checked_add(1)
+            .ok_or(ErrorCode::ArithmeticOverflow)?;
+        review.rating = rating;
+        review.comment = comment;
+        review.updated_at = now;
+
+        emit!(ReviewEdited {
+            review: review.key(),
+            coupon: review.coupon,
+            user: review.user,
+            old_rating,
+            rating,
+            edit_count: review.edit_count,
+            history_hash: review.history_hash,
+            timestamp: now,
+        });
+
+        Ok(())
+    }
+
+    /// Delete a review and return its rent to the author
+    pub fn delete_review(ctx: Context<DeleteReview>) -> Result<()> {
+        let review = &ctx.accounts.review;
+
+        // Hidden reviews stay put so they can't be rewritten to dodge moderation
+        require!(!review.is_hidden, ErrorCode::ReviewHidden);
+
+        // Flags are keyed by the review address, so a resubmitted review would
+        // come back with a clean count that its flaggers can't add to again
+        require!(review.flag_count == 0, ErrorCode::ReviewFlagged);
+
+        remove_rating(
+            &mut ctx.accounts.merchant,
+            &mut ctx.accounts.coupon,
+            review.rating,
+        )?;
+
+        // Free the unit's holder review slot
+        if review.proof == ReviewProof::HeldUnit {
+            let coupon = &mut ctx.accounts.coupon;
+            coupon.holder_reviews = coupon.holder_reviews.saturating_sub(1);
+        }
+
+        // The review can be written again, so one that counted toward
+        // achievements must be taken back out
+        if review.counted_for_achievements {
+            let achievements = ctx
+                .accounts
+                .achievements
+                .as_mut()
+                .ok_or(ErrorCode::AchievementsRequired)?;
+            achievements.reviews = achievements.reviews.saturating_sub(1);
+        }
+
+        // The reply PDA is keyed by the review address, so it would otherwise
+        // attach itself to a resubmitted review
+        if review.has_reply {
+            let reply = ctx
+                .accounts
+                .reply
+                .as_ref()
+                .ok_or(ErrorCode::ReviewReplyRequired)?;
+            let reply_author = ctx
+                .accounts
+                .reply_author
+                .as_ref()
+                .filter(|author| author.key() == reply.author)
+                .ok_or(ErrorCode::ReviewReplyRequired)?;
+            reply.close(reply_author.to_account_info())?;
+        }
+
+        emit!(ReviewDeleted {
+            review: review.key(),
+            coupon: review.coupon,
+            user: review.user,
+            rating: review.rating,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        // Rent returned to the author via close constraint
+        Ok(())
+    }
+
+    /// Start tracking a user's review flags (needed before flagging)
+    pub fn initialize_flagger(ctx: Context<InitializeFlagger>) -> Result<()> {
+        let flagger_state = &mut ctx.accounts.flagger_state;
+        flagger_state.flagger = ctx.accounts.flagger.key();
+        flagger_state.window_start = 0;
+        flagger_state.flags_in_window = 0;
+        flagger_state.total_flags = 0;
+        flagger_state.bump = ctx.bumps.flagger_state;
+
+        Ok(())
+    }
+
+    /// Report a review for moderation (once per review, rate limited per flagger)
+    pub fn flag_review(ctx: Context<FlagReview>, reason: FlagReason) -> Result<()> {
+        let review = &mut ctx.accounts.review;
+        let flagger_state = &mut ctx.accounts.flagger_state;
+        let now = Clock::get()?.unix_timestamp;
+
+        require!(
+            review.user != ctx.accounts.flagger.key(),
+            ErrorCode::CannotFlagOwnReview
+        );
+
+        // Fixed window rate limit
+        if now - flagger_state.window_start >= FLAG_WINDOW {
+            flagger_state.window_start = now;
+            flagger_state.flags_in_window = 0;
+        }
+        require!(
+            flagger_state.flags_in_window < MAX_FLAGS_PER_WINDOW,
+            ErrorCode::FlagRateLimited
+        );
+        flagger_state.flags_in_window += 1;
+        flagger_state.total_flags = flagger_state
+            .total_flags
+            .checked_add(1)
+            .ok_or(ErrorCode::ArithmeticOverflow)?;
+
+        review.flag_count = review
+            .flag_count
+            .checked_add(1)
+            .ok_or(ErrorCode::ArithmeticOverflow)?;
+
+        let flag = &mut ctx.accounts.flag;
+        flag.review = review.key();
+        flag.flagger = ctx.accounts.flagger.key();
+        flag.reason = reason.clone();
+        flag.timestamp = now;
+        flag.bump = ctx.bumps.flag;
+
+        emit!(ReviewFlagged {
+            review: review.key(),
+            flagger: flag.flagger,
+            reason,
+            flag_count: review.flag_count,
+            timestamp: now,
+        });
+
+        Ok(())
+    }
+
+    /// Hide a review and drop it from ratings (admin or moderator)
+    pub fn hide_review(ctx: Context<ModerateReview>, reason_hash: [u8; 32]) -> Result<()> {
+        set_review_hidden(ctx, true, reason_hash)
+    }
+
+    /// Restore a hidden review and count it in ratings again (admin or moderator)
+    pub fn restore_review(ctx: Context<ModerateReview>, reason_hash: [u8; 32]) -> Result<()> {
+        set_review_hidden(ctx, false, reason_hash)
+    }
+
+    /// Publicly answer a review (merchant authority or staff)
+    pub fn reply_to_review(ctx: Context<ReplyToReview>, comment: String) -> Result<()> {
+        validate_comment(&comment)?;
+
+        ctx.accounts.review.has_reply = true;
+
+        let reply = &mut ctx.accounts.reply;
+        reply.review = ctx.accounts.review.key();
+        reply.merchant = ctx.accounts.merchant.key();
+        reply.author = ctx.accounts.author.key();
+        reply.comment = comment;
+        reply.timestamp = Clock::get()?.unix_timestamp;
+        reply.bump = ctx.bumps.reply;
+
+        emit!(ReviewReplied {
+            reply: reply.key(),
+            review: reply.review,
+            merchant: reply.merchant,
+            author: reply.author,
+            timestamp: reply.timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Edit coupon terms without harming existing holders (merchant only)
+    pub fn update_coupon(ctx: Context<UpdateCoupon>, update: CouponUpdate) -> Result<()> {
+        let coupon = &mut ctx.accounts.coupon;
+        let merchant = &ctx.accounts.merchant;
+        let clock = Clock::get()?;
+        let has_holders = coupon.total_purchases > 0;
+        let previous_commitment = committed_discount(
+            coupon.price,
+            coupon.discount_percent,
+            coupon.discount_fixed,
+            coupon.max_redemptions,
+        )?;
+        let mut changes = Vec::new();
+
+        require!(!merchant.is_paused, ErrorCode::MerchantPaused);
+        require!(!merchant.is_closing, ErrorCode::MerchantClosing);
+        require!(
+            clock.unix_timestamp < coupon.expiry_timestamp,
+            ErrorCode::CouponExpired
+        );
+
+        if let Some(price) = update.price {
+            require!(
+                (MIN_PRICE..=MAX_PRICE).contains(&price),
+                ErrorCode::InvalidPrice
+            );
+            require!(
+                is_price_allowed(merchant, &ctx.accounts.config, price),
+                ErrorCode::PriceExceedsUnverifiedLimit
+            );
+            if price != coupon.price {
+                changes.push(CouponChange::Price {
+                    old: coupon.price,
+                    new: price,
+                });
+                coupon.price = price;
+            }
+        }
+
+        if let Some(metadata_uri) = update.metadata_uri {
+            validate_metadata_uri(&metadata_uri)?;
+            if metadata_uri != coupon.metadata_uri {
+                let old = std::mem::replace(&mut coupon.metadata_uri, metadata_uri);
+                changes.push(CouponChange::MetadataUri {
+                    old,
+                    new: coupon.metadata_uri.clone(),
+                });
+            }
+        }
+
+        if let Some(category) = update.category {
+            if category != coupon.category {
+                let new_category = ctx
+                    .accounts
+                    .new_category
+                    .as_mut()
+                    .ok_or(ErrorCode::CategoryRequired)?;
+                require_keys_eq!(new_category.key(), category, ErrorCode::WrongCategory);
+                let old_category = ctx
+                    .accounts
+                    .old_category
+                    .as_mut()
+                    .ok_or(ErrorCode::CategoryRequired)?;
+                move_category_coupon(old_category, new_category)?;
+
+                changes.push(CouponChange::Category {
+                    old: coupon.category,
+                    new: category,
+                });
+                coupon.category = category;
+            }
+        }
+
+        if let Some(max_redemptions) = update.max_redemptions {
+            require!(
+                max_redemptions > 0 && max_redemptions <= MAX_REDEMPTIONS_PER_COUPON,
+                ErrorCode::InvalidQuantity
+            );
+            // Units already sold must stay redeemable
+            require!(
+                max_redemptions >= coupon.total_purchases,
+                ErrorCode::SupplyBelowPurchases
+            );
+            if max_redemptions != coupon.max_redemptions {
+                changes.push(CouponChange::MaxRedemptions {
+                    old: coupon.max_redemptions,
+                    new: max_redemptions,
+                });
+                coupon.max_redemptions = max_redemptions;
+            }
+        }
+
+        if let Some(expiry_timestamp) = update.expiry_timestamp {
+            require!(
+                expiry_timestamp >= coupon.expiry_timestamp,
+                ErrorCode::ExpiryShortened
+            );
+            require!(
+                expiry_timestamp < clock.unix_timestamp + MAX_EXPIRY_DURATION,
+                ErrorCode::ExpiryTooFar
+            );
+            if expiry_timestamp != coupon.expiry_timestamp {
+                changes.push(CouponChange::ExpiryTimestamp {
+                    old: coupon.expiry_timestamp,
+                    new: expiry_timestamp,
+                });
+                coupon.expiry_timestamp = expiry_timestamp;
+            }
+        }
+
+        if let Some(discount_percent) = update.discount_percent {
+            require!(discount_percent <= 100, ErrorCode::InvalidDiscount);
+            require!(
+                !has_holders || discount_percent >= coupon.discount_percent,
+                ErrorCode::DiscountReduced
+            );
+            if discount_percent != coupon.discount_percent {
+                changes.push(CouponChange::DiscountPercent {
+                    old: coupon.discount_percent,
+                    new: discount_percent,
+                });
+                coupon.discount_percent = discount_percent;
+            }
+        }
+
+        if let Some(discount_fixed) = update.discount_fixed {
+            require!(
+                !has_holders || discount_fixed >= coupon.discount_fixed,
+                ErrorCode::DiscountReduced
+            );
+            if discount_fixed != coupon.discount_fixed {
+                changes.push(CouponChange::DiscountFixed {
+                    old: coupon.discount_fixed,
+                    new: discount_fixed,
+                });
+                coupon.discount_fixed = discount_fixed;
+            }
+        }
+
+        require!(!changes.is_empty(), ErrorCode::NoChanges);
+
+        // Move the campaign reservation to the new discount and supply
+        if let Some(campaign) = coupon_campaign(coupon, &mut ctx.accounts.campaign)? {
+            reserve_campaign_budget(
+                campaign,
+                previous_commitment,
+                committed_discount(
+                    coupon.price,
+                    coupon.discount_percent,
+                    coupon.discount_fixed,
+                    coupon.max_redemptions,
+                )?,
+            )?;
+        }
+
+        emit!(CouponUpdated {
+            coupon: coupon.key(),
+            changes,
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Raise a coupon's supply so a popular deal can keep selling (merchant only)
+    pub fn restock_coupon(ctx: Context<RestockCoupon>, additional: u32) -> Result<()> {
+        let coupon = &mut ctx.accounts.coupon;
+        let clock = Clock::get()?;
+
+        require!(!ctx.accounts.merchant.is_paused, ErrorCode::MerchantPaused);
+        require!(
+            !ctx.accounts.merchant.is_closing,
+            ErrorCode::MerchantClosing
+        );
+        require!(additional > 0, ErrorCode::InvalidQuantity);
+        require!(
+            clock.unix_timestamp < coupon.expiry_timestamp,
+            ErrorCode::CouponExpired
+        );
+
+        let max_redemptions = coupon
+            .max_redemptions
+            .checked_add(additional)
+            .ok_or(ErrorCode::ArithmeticOverflow)?;
+        require!(
+            max_redemptions <= MAX_REDEMPTIONS_PER_COUPON,
+            ErrorCode::InvalidQuantity
+        );
+
+        // Extra supply must fit in the campaign budget
+        if let Some(campaign) = coupon_campaign(coupon, &mut ctx.accounts.campaign)? {
+            reserve_campaign_budget(
+                campaign,
+                committed_discount(
+                    coupon.price,
+                    coupon.discount_percent,
+                    coupon.discount_fixed,
+                    coupon.max_redemptions,
+                )?,
+                committed_discount(
+                    coupon.price,
+                    coupon.discount_percent,
+                    coupon.discount_fixed,
+                    max_redemptions,
+                )?,
+            )?;
+        }
+
+        let previous = coupon.max_redemptions;
+        coupon.max_redemptions = max_redemptions;
+
+        emit!(CouponRestocked {
+            coupon: coupon.key(),
+            previous_max_redemptions: previous,
+            max_redemptions,
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
     /// Update coupon status (merchant only)
     pub fn update_coupon_status(ctx: Context<UpdateCouponStatus>, is_active: bool) -> Result<()> {
         let coupon = &mut ctx.accounts.coupon;
@@ -372,510 +1744,3605 @@ pub mod dealchain {
         Ok(())
     }
 
-    /// Initialize loyalty badge
-    pub fn initialize_loyalty_badge(ctx: Context<InitializeLoyaltyBadge>) -> Result<()> {
-        let badge = &mut ctx.accounts.loyalty_badge;
+    /// Start shutting the business down: stops sales and new coupons for good
+    pub fn begin_merchant_wind_down(ctx: Context<BeginMerchantWindDown>) -> Result<()> {
+        let merchant = &mut ctx.accounts.merchant;
+        require!(!merchant.is_closing, ErrorCode::MerchantClosing);
 
-        badge.user = ctx.accounts.user.key();
-        badge.tier = LoyaltyTier::Bronze;
-        badge.deals_purchased = 0;
-        badge.total_saved = 0;
-        badge.points = 0;
-        badge.created_at = Clock::get()?.unix_timestamp;
-        badge.bump = ctx.bumps.loyalty_badge;
+        merchant.is_closing = true;
 
-        emit!(LoyaltyBadgeCreated {
-            badge: badge.key(),
-            user: badge.user,
-            timestamp: badge.created_at,
+        emit!(MerchantWindDownStarted {
+            merchant: merchant.key(),
+            open_coupons: merchant.open_coupons,
+            timestamp: Clock::get()?.unix_timestamp,
         });
 
         Ok(())
     }
 
-    /// Update loyalty badge after verified purchase
-    /// SECURED: Can only be called via CPI from purchase_coupon
-    pub fn update_loyalty_badge(
-        ctx: Context<UpdateLoyaltyBadge>,
-        purchase_amount: u64,
-        savings_amount: u64,
-    ) -> Result<()> {
-        let badge = &mut ctx.accounts.loyalty_badge;
+    /// Close a wound-down merchant once every coupon is closed. The account
+    /// stays as a tombstone so coupons, campaigns, stamp cards and reviews
+    /// keyed by it still resolve and its seed cannot be reused. Unclaimed
+    /// stamp rewards lapse once the last reward coupon has closed.
+    pub fn close_merchant(ctx: Context<CloseMerchant>) -> Result<()> {
+        let merchant = &mut ctx.accounts.merchant;
 
-        // Validate reasonable values
-        require!(purchase_amount <= MAX_PRICE, ErrorCode::InvalidAmount);
-        require!(savings_amount <= MAX_PRICE, ErrorCode::InvalidAmount);
+        require!(merchant.is_closing, ErrorCode::MerchantNotClosing);
+        require!(!merchant.is_closed, ErrorCode::MerchantClosed);
+        require!(merchant.open_coupons == 0, ErrorCode::OpenCouponsRemain);
 
-        // Safe updates with overflow protection
-        badge.deals_purchased = badge
-            .deals_purchased
-            .checked_add(1)
-            .ok_or(ErrorCode::ArithmeticOverflow)?;
+        // Keep the name for anything still pointing here; drop the rest
+        merchant.is_closed = true;
+        merchant.description = String::new();
+        merchant.logo_uri = String::new();
+        merchant.website = String::new();
+        merchant.pending_authority = None;
 
-        badge.total_saved = badge
-            .total_saved
-            .checked_add(savings_amount)
-            .ok_or(ErrorCode::ArithmeticOverflow)?;
+        emit!(MerchantClosed {
+            merchant: merchant.key(),
+            authority: merchant.authority,
+            total_coupons_created: merchant.total_coupons_created,
+            total_redemptions: merchant.total_redemptions,
+            total_revenue: merchant.total_revenue,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
 
-        // 1 point per USDC spent (price in lamports / 1M)
-        let new_points = (purchase_amount / 1_000_000) as u32;
-        badge.points = badge
-            .points
-            .checked_add(new_points)
-            .ok_or(ErrorCode::ArithmeticOverflow)?;
+        // Surplus rent from shrinking to a tombstone returned via realloc
+        Ok(())
+    }
 
-        // Update tier based on points
-        badge.tier = match badge.points {
-            0..=99 => LoyaltyTier::Bronze,
-            100..=499 => LoyaltyTier::Silver,
-            500..=999 => LoyaltyTier::Gold,
-            _ => LoyaltyTier::Platinum,
-        };
+    /// Grant a staff member the right to reply to reviews (merchant only)
+    pub fn add_merchant_staff(ctx: Context<UpdateMerchantStaff>, staff: Pubkey) -> Result<()> {
+        let merchant = &mut ctx.accounts.merchant;
+        require!(
+            !merchant.staff.contains(&staff),
+            ErrorCode::StaffAlreadyExists
+        );
+        require!(
+            merchant.staff.len() < MAX_MERCHANT_STAFF,
+            ErrorCode::TooManyStaff
+        );
 
-        emit!(LoyaltyBadgeUpdated {
-            badge: badge.key(),
-            user: badge.user,
-            tier: badge.tier.clone(),
-            points: badge.points,
+        merchant.staff.push(staff);
+
+        emit!(MerchantStaffUpdated {
+            merchant: merchant.key(),
+            staff,
+            is_staff: true,
+            timestamp: Clock::get()?.unix_timestamp,
         });
 
         Ok(())
     }
 
-    /// Close expired coupon to reclaim rent
-    pub fn close_expired_coupon(ctx: Context<CloseExpiredCoupon>) -> Result<()> {
-        let coupon = &ctx.accounts.coupon;
-        let clock = Clock::get()?;
+    /// Revoke a staff member (merchant only)
+    pub fn remove_merchant_staff(ctx: Context<UpdateMerchantStaff>, staff: Pubkey) -> Result<()> {
+        let merchant = &mut ctx.accounts.merchant;
+        let position = merchant
+            .staff
+            .iter()
+            .position(|s| *s == staff)
+            .ok_or(ErrorCode::StaffNotFound)?;
 
-        // Must be expired + grace period
-        require!(
-            clock.unix_timestamp > coupon.expiry_timestamp + GRACE_PERIOD,
-            ErrorCode::CouponNotExpiredYet
-        );
+        merchant.staff.swap_remove(position);
+
+        emit!(MerchantStaffUpdated {
+            merchant: merchant.key(),
+            staff,
+            is_staff: false,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
 
-        // Rent automatically returned to merchant via close constraint
         Ok(())
     }
-}
 
-// ============ VALIDATION HELPERS ============
+    /// Set per-tier purchase discounts (merchant only)
+    pub fn set_tier_discounts(
+        ctx: Context<SetTierDiscounts>,
+        discounts_bps: [u16; 4],
+    ) -> Result<()> {
+        // Each tier gets at least the discount of the tier below it
+        require!(
+            discounts_bps
+                .iter()
+                .all(|bps| *bps <= MAX_TIER_DISCOUNT_BPS),
+            ErrorCode::InvalidTierDiscount
+        );
+        require!(
+            discounts_bps.windows(2).all(|pair| pair[0] <= pair[1]),
+            ErrorCode::InvalidTierDiscount
+        );
 
-fn is_valid_utf8(s: &str) -> bool {
-    s.chars()
-        .all(|c| !c.is_control() || c == '\n' || c == '\r' || c == '\t')
-}
+        let merchant = &mut ctx.accounts.merchant;
+        merchant.tier_discount_bps = discounts_bps;
 
-fn is_valid_uri(uri: &str) -> bool {
-    // Basic URI validation (starts with ipfs:// or https://)
-    uri.starts_with("ipfs://") || uri.starts_with("https://") || uri.starts_with("ar://")
-}
+        emit!(TierDiscountsUpdated {
+            merchant: merchant.key(),
+            discounts_bps,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
 
-// ============ ACCOUNT CONTEXTS ============
+        Ok(())
+    }
 
-#[derive(Accounts)]
-#[instruction(name: String)]
-pub struct InitializeMerchant<'info> {
-    #[account(
-        init,
-        payer = authority,
-        space = 8 + Merchant::INIT_SPACE,
-        seeds = [b"merchant", authority.key().as_ref()],
-        bump
-    )]
-    pub merchant: Account<'info, Merchant>,
+    /// Create a stamp-card program rewarding every Nth redemption (merchant only)
+    pub fn create_loyalty_program(
+        ctx: Context<CreateLoyaltyProgram>,
+        stamps_required: u16,
+    ) -> Result<()> {
+        require!(
+            stamps_required > 0 && stamps_required <= MAX_STAMPS_REQUIRED,
+            ErrorCode::InvalidStampsRequired
+        );
 
-    #[account(mut)]
-    pub authority: Signer<'info>,
+        let program = &mut ctx.accounts.loyalty_program;
+        program.merchant = ctx.accounts.merchant.key();
+        program.reward_coupon = ctx.accounts.reward_coupon.key();
+        program.stamps_required = stamps_required;
+        program.is_active = true;
+        program.rewards_issued = 0;
+        program.created_at = Clock::get()?.unix_timestamp;
+        program.bump = ctx.bumps.loyalty_program;
+
+        emit!(LoyaltyProgramUpdated {
+            loyalty_program: program.key(),
+            merchant: program.merchant,
+            reward_coupon: program.reward_coupon,
+            stamps_required,
+            is_active: true,
+            timestamp: program.created_at,
+        });
 
-    pub system_program: Program<'info, System>,
-}
+        Ok(())
+    }
 
-#[derive(Accounts)]
-pub struct CreateCoupon<'info> {
-    #[account(
-        init,
-        payer = authority,
-        space = 8 + Coupon::INIT_SPACE,
-        seeds = [b"coupon", mint.key().as_ref()],
-        bump
-    )]
-    pub coupon: Account<'info, Coupon>,
+    /// Change the stamp threshold or pause the program (merchant only)
+    pub fn update_loyalty_program(
+        ctx: Context<UpdateLoyaltyProgram>,
+        stamps_required: u16,
+        is_active: bool,
+    ) -> Result<()> {
+        require!(
+            stamps_required > 0 && stamps_required <= MAX_STAMPS_REQUIRED,
+            ErrorCode::InvalidStampsRequired
+        );
 
-    #[account(
-        mut,
-        seeds = [b"merchant", authority.key().as_ref()],
-        bump = merchant.bump,
-        has_one = authority @ ErrorCode::Unauthorized
-    )]
-    pub merchant: Account<'info, Merchant>,
+        let program = &mut ctx.accounts.loyalty_program;
+        program.stamps_required = stamps_required;
+        program.is_active = is_active;
 
-    /// Mint must not have freeze authority (security)
-    #[account(
-        constraint = mint.freeze_authority.is_none() @ ErrorCode::MintMustNotHaveFreezeAuthority
-    )]
-    pub mint: Account<'info, Mint>,
+        emit!(LoyaltyProgramUpdated {
+            loyalty_program: program.key(),
+            merchant: program.merchant,
+            reward_coupon: program.reward_coupon,
+            stamps_required,
+            is_active,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
 
-    #[account(mut)]
-    pub authority: Signer<'info>,
+        Ok(())
+    }
 
-    pub system_program: Program<'info, System>,
-}
+    /// Open a stamp card with a merchant
+    pub fn initialize_stamp_card(ctx: Context<InitializeStampCard>) -> Result<()> {
+        let card = &mut ctx.accounts.stamp_card;
+        card.merchant = ctx.accounts.merchant.key();
+        card.customer = ctx.accounts.customer.key();
+        card.stamps = 0;
+        card.rewards_available = 0;
+        card.rewards_claimed = 0;
+        card.bump = ctx.bumps.stamp_card;
 
-#[derive(Accounts)]
-pub struct PurchaseCoupon<'info> {
-    #[account(
-        mut,
-        seeds = [b"coupon", coupon.mint.as_ref()],
+        Ok(())
+    }
+
+    /// Claim one earned reward: mints a unit of the merchant's reward coupon
+    pub fn claim_stamp_reward(ctx: Context<ClaimStampReward>) -> Result<()> {
+        let card = &mut ctx.accounts.stamp_card;
+        let program = &mut ctx.accounts.loyalty_program;
+        let coupon = &mut ctx.accounts.reward_coupon;
+        let clock = Clock::get()?;
+
+        require!(card.rewards_available > 0, ErrorCode::NoRewardAvailable);
+        require!(!ctx.accounts.merchant.is_paused, ErrorCode::MerchantPaused);
+        // Rewards already earned stay claimable during wind-down while the
+        // reward coupon is still open
+        require!(!coupon.is_closed, ErrorCode::CouponClosed);
+        require!(coupon.is_active, ErrorCode::CouponInactive);
+        require!(
+            clock.unix_timestamp < coupon.expiry_timestamp,
+            ErrorCode::CouponExpired
+        );
+        require!(
+            coupon.total_purchases < coupon.max_redemptions,
+            ErrorCode::SoldOut
+        );
+
+        // Free units of a campaign coupon obey its window and spend its
+        // budget like a full-price discount
+        if let Some(campaign) = coupon_campaign(coupon, &mut ctx.accounts.campaign)? {
+            require!(
+                clock.unix_timestamp >= campaign.start_timestamp
+                    && clock.unix_timestamp < campaign.end_timestamp,
+                ErrorCode::CampaignNotActive
+            );
+            charge_campaign_budget(campaign, coupon.price)?;
+            campaign.total_purchases = campaign
+                .total_purchases
+                .checked_add(1)
+                .ok_or(ErrorCode::ArithmeticOverflow)?;
+        }
+
+        // Reward units come out of the coupon's regular supply
+        coupon.total_purchases = coupon
+            .total_purchases
+            .checked_add(1)
+            .ok_or(ErrorCode::ArithmeticOverflow)?;
+        card.rewards_available -= 1;
+        card.rewards_claimed = card
+            .rewards_claimed
+            .checked_add(1)
+            .ok_or(ErrorCode::ArithmeticOverflow)?;
+        program.rewards_issued = program
+            .rewards_issued
+            .checked_add(1)
+            .ok_or(ErrorCode::ArithmeticOverflow)?;
+
+        // Program PDA is the reward mint's authority
+        let merchant_key = program.merchant;
+        let program_seeds: &[&[u8]] = &[b"loyalty_program", merchant_key.as_ref(), &[program.bump]];
+        let signer_seeds = &[program_seeds];
+        token::mint_to(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                MintTo {
+                    mint: ctx.accounts.reward_mint.to_account_info(),
+                    to: ctx.accounts.customer_reward_account.to_account_info(),
+                    authority: program.to_account_info(),
+                },
+                signer_seeds,
+            ),
+            1,
+        )?;
+
+        emit!(StampRewardClaimed {
+            stamp_card: card.key(),
+            merchant: merchant_key,
+            customer: card.customer,
+            reward_coupon: coupon.key(),
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Initialize loyalty badge
+    pub fn initialize_loyalty_badge(ctx: Context<InitializeLoyaltyBadge>) -> Result<()> {
+        let badge = &mut ctx.accounts.loyalty_badge;
+
+        badge.user = ctx.accounts.user.key();
+        badge.tier = LoyaltyTier::Bronze;
+        badge.deals_purchased = 0;
+        badge.total_saved = 0;
+        badge.points = 0;
+        badge.tier_points = 0;
+        badge.created_at = Clock::get()?.unix_timestamp;
+        badge.last_decay_at = badge.created_at;
+        badge.badge_mint = ctx.accounts.badge_mint.key();
+        badge.nft_tier = LoyaltyTier::Bronze;
+        badge.nft_uri_version = ctx.accounts.config.badge_uri_version;
+        badge.bump = ctx.bumps.loyalty_badge;
+
+        mint_soulbound_badge(ctx.accounts, ctx.bumps.badge_mint, ctx.bumps.loyalty_badge)?;
+
+        let badge = &ctx.accounts.loyalty_badge;
+        emit!(LoyaltyBadgeCreated {
+            badge: badge.key(),
+            user: badge.user,
+            timestamp: badge.created_at,
+        });
+
+        Ok(())
+    }
+
+    /// Write the badge's current tier into its NFT metadata (permissionless)
+    pub fn sync_loyalty_badge_nft(ctx: Context<SyncLoyaltyBadgeNft>) -> Result<()> {
+        // Apply pending decay first so the NFT never shows a lapsed tier
+        refresh_badge(
+            &mut ctx.accounts.loyalty_badge,
+            &ctx.accounts.config,
+            Clock::get()?.unix_timestamp,
+        )?;
+
+        let badge = &ctx.accounts.loyalty_badge;
+        let uri_version = ctx.accounts.config.badge_uri_version;
+        require!(
+            badge.nft_tier != badge.tier || badge.nft_uri_version != uri_version,
+            ErrorCode::BadgeNftUpToDate
+        );
+
+        let tier = badge.tier.clone();
+        let user_key = badge.user;
+        let badge_seeds: &[&[u8]] = &[b"loyalty", user_key.as_ref(), &[badge.bump]];
+        let token_program_id = ctx.accounts.token_2022_program.key();
+        let mint_key = ctx.accounts.badge_mint.key();
+        let badge_key = badge.key();
+        let accounts = [
+            ctx.accounts.badge_mint.to_account_info(),
+            badge.to_account_info(),
+        ];
+
+        for (field, value) in [
+            (Field::Name, badge_nft_name(&tier)),
+            (
+                Field::Uri,
+                badge_nft_uri(&ctx.accounts.config.badge_uri_base, &tier),
+            ),
+            (
+                Field::Key(BADGE_TIER_KEY.to_string()),
+                tier.name().to_string(),
+            ),
+        ] {
+            invoke_signed(
+                &spl_token_metadata_interface::instruction::update_field(
+                    &token_program_id,
+                    &mint_key,
+                    &badge_key,
+                    field,
+                    value,
+                ),
+                &accounts,
+                &[badge_seeds],
+            )?;
+        }
+
+        ctx.accounts.loyalty_badge.nft_tier = tier.clone();
+        ctx.accounts.loyalty_badge.nft_uri_version = uri_version;
+
+        emit!(LoyaltyBadgeNftSynced {
+            badge: badge_key,
+            badge_mint: mint_key,
+            tier,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Start tracking achievement progress for a user
+    pub fn initialize_achievements(ctx: Context<InitializeAchievements>) -> Result<()> {
+        let achievements = &mut ctx.accounts.achievements;
+        achievements.user = ctx.accounts.user.key();
+        achievements.redemptions = 0;
+        achievements.reviews = 0;
+        achievements.categories_redeemed = Vec::new();
+        achievements.claimed = 0;
+        achievements.bump = ctx.bumps.achievements;
+
+        Ok(())
+    }
+
+    /// Claim a completed achievement and receive its bonus points
+    pub fn claim_achievement(ctx: Context<ClaimAchievement>) -> Result<()> {
+        let achievement = &ctx.accounts.achievement;
+        let achievements = &mut ctx.accounts.achievements;
+        let badge = &mut ctx.accounts.loyalty_badge;
+        let now = Clock::get()?.unix_timestamp;
+
+        require!(achievement.is_active, ErrorCode::AchievementInactive);
+        let flag = 1u64 << achievement.id;
+        require!(
+            achievements.claimed & flag == 0,
+            ErrorCode::AchievementAlreadyClaimed
+        );
+
+        let progress = match achievement.kind {
+            AchievementKind::RedemptionCount => achievements.redemptions,
+            AchievementKind::ReviewCount => achievements.reviews,
+            AchievementKind::CategoriesRedeemed => achievements.categories_redeemed.len() as u32,
+        };
+        require!(
+            progress >= achievement.target,
+            ErrorCode::AchievementNotCompleted
+        );
+
+        achievements.claimed |= flag;
+
+        // Bonus points count toward both the balance and the tier
+        let config = &ctx.accounts.config;
+        let rules = &config.loyalty_rules;
+        refresh_badge(badge, config, now)?;
+        badge.points = badge
+            .points
+            .checked_add(achievement.reward_points)
+            .ok_or(ErrorCode::ArithmeticOverflow)?;
+        badge.tier_points = badge
+            .tier_points
+            .checked_add(achievement.reward_points)
+            .ok_or(ErrorCode::ArithmeticOverflow)?;
+        badge.tier = tier_for_points(badge.tier_points, &rules.tier_thresholds);
+
+        emit!(AchievementClaimed {
+            achievement: achievement.key(),
+            id: achievement.id,
+            user: achievements.user,
+            reward_points: achievement.reward_points,
+            timestamp: now,
+        });
+
+        Ok(())
+    }
+
+    /// Apply pending point decay and recompute the tier (permissionless crank)
+    pub fn refresh_loyalty_badge(ctx: Context<RefreshLoyaltyBadge>) -> Result<()> {
+        let badge = &mut ctx.accounts.loyalty_badge;
+        let now = Clock::get()?.unix_timestamp;
+
+        refresh_badge(badge, &ctx.accounts.config, now)?;
+
+        emit!(LoyaltyBadgeUpdated {
+            badge: badge.key(),
+            user: badge.user,
+            tier: badge.tier.clone(),
+            points: badge.points,
+        });
+
+        Ok(())
+    }
+
+    /// Close an expired coupon: escrow refunds for unredeemed units and shrink
+    /// the account to a tombstone so reviews and redemption records still resolve
+    pub fn close_expired_coupon(ctx: Context<CloseExpiredCoupon>) -> Result<()> {
+        let coupon = &mut ctx.accounts.coupon;
+        let merchant = &mut ctx.accounts.merchant;
+        let clock = Clock::get()?;
+
+        let outstanding = coupon
+            .total_purchases
+            .saturating_sub(coupon.current_redemptions);
+
+        // Must be expired + grace period; a winding-down merchant may close
+        // early only when no holder is left to redeem
+        require!(
+            clock.unix_timestamp > coupon.expiry_timestamp + GRACE_PERIOD
+                || (merchant.is_closing && outstanding == 0),
+            ErrorCode::CouponNotExpiredYet
+        );
+
+        // Every unredeemed unit is refunded from escrow at the average price
+        // buyers actually paid, so free reward units add nothing to the pool
+        let refund_per_unit = average_paid_per_unit(coupon.paid_total, coupon.total_purchases);
+        let refund_total = refund_per_unit
+            .checked_mul(outstanding as u64)
+            .ok_or(ErrorCode::ArithmeticOverflow)?;
+
+        coupon.is_closed = true;
+        coupon.is_active = false;
+        coupon.metadata_uri = String::new();
+        coupon.refund_per_unit = refund_per_unit;
+        coupon.refunds_remaining = outstanding;
+
+        merchant.open_coupons = merchant.open_coupons.saturating_sub(1);
+
+        let category = &mut ctx.accounts.category;
+        category.coupon_count = category
+            .coupon_count
+            .checked_sub(1)
+            .ok_or(ErrorCode::ArithmeticOverflow)?;
+
+        if refund_total > 0 {
+            let vault = ctx
+                .accounts
+                .refund_vault
+                .as_ref()
+                .ok_or(ErrorCode::RefundVaultRequired)?;
+            let merchant_token_account = ctx
+                .accounts
+                .merchant_token_account
+                .as_ref()
+                .ok_or(ErrorCode::RefundVaultRequired)?;
+            require!(
+                merchant_token_account.amount >= refund_total,
+                ErrorCode::InsufficientFunds
+            );
+
+            let cpi_accounts = Transfer {
+                from: merchant_token_account.to_account_info(),
+                to: vault.to_account_info(),
+                authority: ctx.accounts.merchant_authority.to_account_info(),
+            };
+            token::transfer(
+                CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts),
+                refund_total,
+            )?;
+        }
+
+        emit!(CouponClosed {
+            coupon: coupon.key(),
+            merchant: merchant.key(),
+            outstanding,
+            refund_per_unit: coupon.refund_per_unit,
+            timestamp: clock.unix_timestamp,
+        });
+
+        // Surplus rent from shrinking to a tombstone returned via realloc
+        Ok(())
+    }
+
+    /// Burn an unredeemed NFT of a closed coupon for its escrowed refund
+    pub fn claim_refund(ctx: Context<ClaimRefund>) -> Result<()> {
+        let coupon = &mut ctx.accounts.coupon;
+
+        require!(coupon.refunds_remaining > 0, ErrorCode::NoRefundAvailable);
+        coupon.refunds_remaining -= 1;
+
+        // SECURITY: Burn the NFT first so a unit can only be refunded once
+        let burn_accounts = Burn {
+            mint: ctx.accounts.mint.to_account_info(),
+            from: ctx.accounts.holder_nft_account.to_account_info(),
+            authority: ctx.accounts.holder.to_account_info(),
+        };
+        token::burn(
+            CpiContext::new(ctx.accounts.token_program.to_account_info(), burn_accounts),
+            1,
+        )?;
+
+        // Vault is owned by the coupon PDA
+        let coupon_seeds: &[&[u8]] = &[b"coupon", coupon.mint.as_ref(), &[coupon.bump]];
+        let signer_seeds = &[coupon_seeds];
+        let cpi_accounts = Transfer {
+            from: ctx.accounts.refund_vault.to_account_info(),
+            to: ctx.accounts.holder_token_account.to_account_info(),
+            authority: coupon.to_account_info(),
+        };
+        token::transfer(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                cpi_accounts,
+                signer_seeds,
+            ),
+            coupon.refund_per_unit,
+        )?;
+
+        emit!(RefundClaimed {
+            coupon: coupon.key(),
+            holder: ctx.accounts.holder.key(),
+            amount: coupon.refund_per_unit,
+            refunds_remaining: coupon.refunds_remaining,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        Ok(())
+    }
+}
+
+// ============ VALIDATION HELPERS ============
+
+/// Unverified merchants may be limited to low-priced deals
+fn is_price_allowed(merchant: &Merchant, config: &GlobalConfig, price: u64) -> bool {
+    merchant.is_verified || config.unverified_max_price == 0 || price <= config.unverified_max_price
+}
+
+/// Shared by merchant creation and profile updates (logo and website optional)
+fn validate_merchant_profile(
+    name: &str,
+    description: &str,
+    logo_uri: &str,
+    website: &str,
+) -> Result<()> {
+    require!(!name.is_empty(), ErrorCode::NameEmpty);
+    require!(name.len() <= MAX_MERCHANT_NAME_LEN, ErrorCode::NameTooLong);
+    require!(
+        description.len() <= MAX_DESCRIPTION_LEN,
+        ErrorCode::DescriptionTooLong
+    );
+    require!(is_valid_utf8(name), ErrorCode::InvalidUtf8);
+    require!(is_valid_utf8(description), ErrorCode::InvalidUtf8);
+
+    require!(logo_uri.len() <= MAX_URI_LEN, ErrorCode::UriTooLong);
+    require!(
+        logo_uri.is_empty() || is_valid_uri(logo_uri),
+        ErrorCode::InvalidUri
+    );
+    require!(website.len() <= MAX_WEBSITE_LEN, ErrorCode::UriTooLong);
+    require!(
+        website.is_empty() || website.starts_with("https://"),
+        ErrorCode::InvalidWebsite
+    );
+    require!(is_valid_utf8(website), ErrorCode::InvalidUtf8);
+
+    Ok(())
+}
+
+fn validate_metadata_uri(metadata_uri: &str) -> Result<()> {
+    require!(!metadata_uri.is_empty(), ErrorCode::UriEmpty);
+    require!(metadata_uri.len() <= MAX_URI_LEN, ErrorCode::UriTooLong);
+    require!(is_valid_uri(metadata_uri), ErrorCode::InvalidUri);
+    Ok(())
+}
+
+/// Shared by category creation and updates (icon optional)
+fn validate_category(name: &str, icon_uri: &str) -> Result<()> {
+    require!(!name.is_empty(), ErrorCode::NameEmpty);
+    require!(name.len() <= MAX_CATEGORY_NAME_LEN, ErrorCode::NameTooLong);
+    require!(is_valid_utf8(name), ErrorCode::InvalidUtf8);
+    require!(icon_uri.len() <= MAX_URI_LEN, ErrorCode::UriTooLong);
+    require!(
+        icon_uri.is_empty() || is_valid_uri(icon_uri),
+        ErrorCode::InvalidUri
+    );
+    Ok(())
+}
+
+fn validate_config_change(change: &ConfigChange) -> Result<()> {
+    match change {
+        ConfigChange::PointsConfig { point_value, .. } => {
+            require!(*point_value > 0, ErrorCode::InvalidPointValue);
+        }
+        ConfigChange::LoyaltyRules(rules) => {
+            require!(
+                is_valid_loyalty_rules(rules),
+                ErrorCode::InvalidLoyaltyRules
+            );
+        }
+        ConfigChange::UnverifiedMaxPrice(unverified_max_price) => {
+            require!(
+                *unverified_max_price == 0 || *unverified_max_price >= MIN_PRICE,
+                ErrorCode::InvalidPrice
+            );
+        }
+        ConfigChange::CategoryMultiplier { multiplier_bps, .. } => {
+            require!(
+                *multiplier_bps <= MAX_CATEGORY_MULTIPLIER_BPS,
+                ErrorCode::InvalidLoyaltyRules
+            );
+        }
+    }
+    Ok(())
+}
+
+fn is_valid_gate(gate: &CouponGate) -> bool {
+    // A mint gate needs a positive balance; no mint means no balance requirement
+    match gate.required_mint {
+        Some(_) => gate.required_amount > 0,
+        None => gate.required_amount == 0,
+    }
+}
+
+fn is_valid_loyalty_rules(rules: &LoyaltyRules) -> bool {
+    // Thresholds must be positive and strictly increasing by tier
+    rules.tier_thresholds[0] > 0
+        && rules
+            .tier_thresholds
+            .windows(2)
+            .all(|pair| pair[0] < pair[1])
+        && rules.points_per_token <= MAX_POINTS_PER_TOKEN
+        && (rules.decay_period == 0 || rules.decay_period >= MIN_DECAY_PERIOD)
+        && rules.decay_bps as u64 <= BPS_DENOMINATOR
+}
+
+/// Shared by reviews and merchant replies
+fn validate_comment(comment: &str) -> Result<()> {
+    require!(comment.len() <= MAX_COMMENT_LEN, ErrorCode::CommentTooLong);
+    require!(is_valid_utf8(comment), ErrorCode::InvalidUtf8);
+    Ok(())
+}
+
+fn is_valid_utf8(s: &str) -> bool {
+    s.chars()
+        .all(|c| !c.is_control() || c == '\n' || c == '\r' || c == '\t')
+}
+
+fn is_valid_uri(uri: &str) -> bool {
+    // Basic URI validation (starts with ipfs:// or https://)
+    uri.starts_with("ipfs://") || uri.starts_with("https://") || uri.starts_with("ar://")
+}
+
+// ============ COLLECTION HELPERS ============
+
+/// Whether `metadata` is the Metaplex metadata of `nft_mint` and names
+/// `collection` as its verified collection
+fn is_collection_member(
+    metadata: &AccountInfo,
+    nft_mint: &Pubkey,
+    collection: &Pubkey,
+) -> Result<bool> {
+    if metadata.owner != &token_metadata::ID {
+        return Ok(false);
+    }
+    let (expected, _) = Pubkey::find_program_address(
+        &[b"metadata", token_metadata::ID.as_ref(), nft_mint.as_ref()],
+        &token_metadata::ID,
+    );
+    if metadata.key() != expected {
+        return Ok(false);
+    }
+    let data = metadata.try_borrow_data()?;
+    Ok(verified_collection(&data, nft_mint) == Some(*collection))
+}
+
+/// Verified collection recorded in Metaplex metadata for `mint`, if any.
+/// Reads the Borsh prefix of the account up to the collection field.
+fn verified_collection(data: &[u8], mint: &Pubkey) -> Option<Pubkey> {
+    let reader = &mut &data[..];
+    if u8::deserialize(reader).ok()? != METADATA_V1_KEY {
+        return None;
+    }
+    let _update_authority = Pubkey::deserialize(reader).ok()?;
+    if Pubkey::deserialize(reader).ok()? != *mint {
+        return None;
+    }
+    // Name, symbol and uri (raw bytes; on-chain strings are null-padded)
+    for _ in 0..3 {
+        Vec::<u8>::deserialize(reader).ok()?;
+    }
+    let _seller_fee_basis_points = u16::deserialize(reader).ok()?;
+    let _creators = Option::<Vec<(Pubkey, bool, u8)>>::deserialize(reader).ok()?;
+    let _primary_sale_happened = bool::deserialize(reader).ok()?;
+    let _is_mutable = bool::deserialize(reader).ok()?;
+    let _edition_nonce = Option::<u8>::deserialize(reader).ok()?;
+    let _token_standard = Option::<u8>::deserialize(reader).ok()?;
+    match Option::<(bool, Pubkey)>::deserialize(reader).ok()? {
+        Some((true, key)) => Some(key),
+        _ => None,
+    }
+}
+
+// ============ CONFIG HELPERS ============
+
+/// Write a validated timelocked change into the config (or its category)
+fn apply_config_change(
+    config: &mut GlobalConfig,
+    category: Option<&mut Account<Category>>,
+    change: &ConfigChange,
+    now: i64,
+) -> Result<()> {
+    match change {
+        ConfigChange::PointsConfig {
+            point_value,
+            points_funding,
+        } => {
+            config.point_value = *point_value;
+            config.points_funding = points_funding.clone();
+
+            emit!(PointsConfigUpdated {
+                point_value: *point_value,
+                points_funding: points_funding.clone(),
+                timestamp: now,
+            });
+        }
+        ConfigChange::LoyaltyRules(rules) => {
+            // Decay only runs from the moment it is switched on
+            if is_decay_enabled(rules) && !is_decay_enabled(&config.loyalty_rules) {
+                config.decay_enabled_at = now;
+            }
+            config.loyalty_rules = rules.clone();
+
+            emit!(LoyaltyRulesUpdated {
+                rules: rules.clone(),
+                timestamp: now,
+            });
+        }
+        ConfigChange::UnverifiedMaxPrice(unverified_max_price) => {
+            config.unverified_max_price = *unverified_max_price;
+
+            emit!(UnverifiedMaxPriceUpdated {
+                unverified_max_price: *unverified_max_price,
+                timestamp: now,
+            });
+        }
+        ConfigChange::CategoryMultiplier {
+            category: key,
+            multiplier_bps,
+        } => {
+            let category = category.ok_or(ErrorCode::CategoryRequired)?;
+            require_keys_eq!(category.key(), *key, ErrorCode::WrongCategory);
+            category.points_multiplier_bps = *multiplier_bps;
+
+            emit!(CategoryMultiplierUpdated {
+                category: *key,
+                multiplier_bps: *multiplier_bps,
+                timestamp: now,
+            });
+        }
+    }
+    Ok(())
+}
+
+// ============ CATEGORY HELPERS ============
+
+/// Move one coupon's count from its old category to its new one
+fn move_category_coupon(old: &mut Category, new: &mut Category) -> Result<()> {
+    old.coupon_count = old
+        .coupon_count
+        .checked_sub(1)
+        .ok_or(ErrorCode::ArithmeticOverflow)?;
+    new.coupon_count = new
+        .coupon_count
+        .checked_add(1)
+        .ok_or(ErrorCode::ArithmeticOverflow)?;
+    Ok(())
+}
+
+// ============ REVIEW HELPERS ============
+
+/// A reviewer must hold the coupon NFT or have redeemed the coupon. A
+/// redemption record is preferred: it is bound to the user and can't be
+/// handed on, so it doesn't use up a holder review slot.
+fn review_proof(
+    coupon: &Pubkey,
+    coupon_mint: &Pubkey,
+    user: &Pubkey,
+    nft_account: Option<&spl_token::state::Account>,
+    redemption_record: Option<&RedemptionRecord>,
+) -> Option<ReviewProof> {
+    let has_redeemed =
+        redemption_record.is_some_and(|record| record.coupon == *coupon && record.user == *user);
+    let owns_nft = nft_account.is_some_and(|account| {
+        account.mint == *coupon_mint && account.owner == *user && account.amount >= 1
+    });
+
+    if has_redeemed {
+        Some(ReviewProof::Redemption)
+    } else if owns_nft {
+        Some(ReviewProof::HeldUnit)
+    } else {
+        None
+    }
+}
+
+/// Whether an unredeemed unit is left to back another holder review
+fn has_holder_review_slot(holder_reviews: u32, units_sold: u32, units_redeemed: u32) -> bool {
+    holder_reviews < units_sold.saturating_sub(units_redeemed)
+}
+
+/// Move a held-unit review onto its author's redemption, freeing the holder slot
+fn release_holder_review(proof: &mut ReviewProof, holder_reviews: &mut u32) {
+    if *proof == ReviewProof::HeldUnit {
+        *proof = ReviewProof::Redemption;
+        *holder_reviews = holder_reviews.saturating_sub(1);
+    }
+}
+
+/// Commit to the current version of a review, chained onto earlier versions
+fn review_history_hash(review: &Review) -> [u8; 32] {
+    hashv(&[
+        &review.history_hash,
+        &[review.rating],
+        review.comment.as_bytes(),
+        &review.updated_at.to_le_bytes(),
+    ])
+    .to_bytes()
+}
+
+fn set_review_hidden(
+    ctx: Context<ModerateReview>,
+    is_hidden: bool,
+    reason_hash: [u8; 32],
+) -> Result<()> {
+    let review = &mut ctx.accounts.review;
+    require!(review.is_hidden != is_hidden, ErrorCode::NoChanges);
+
+    if is_hidden {
+        remove_rating(
+            &mut ctx.accounts.merchant,
+            &mut ctx.accounts.coupon,
+            review.rating,
+        )?;
+    } else {
+        add_rating(
+            &mut ctx.accounts.merchant,
+            &mut ctx.accounts.coupon,
+            review.rating,
+        )?;
+    }
+    review.is_hidden = is_hidden;
+
+    emit!(ReviewModerated {
+        review: review.key(),
+        moderator: ctx.accounts.moderator.key(),
+        is_hidden,
+        reason_hash,
+        timestamp: Clock::get()?.unix_timestamp,
+    });
+
+    Ok(())
+}
+
+/// Count a rating toward the merchant and coupon aggregates
+fn add_rating(merchant: &mut Merchant, coupon: &mut Coupon, rating: u8) -> Result<()> {
+    merchant.rating_sum = merchant
+        .rating_sum
+        .checked_add(rating as u64)
+        .ok_or(ErrorCode::ArithmeticOverflow)?;
+    merchant.rating_count = merchant
+        .rating_count
+        .checked_add(1)
+        .ok_or(ErrorCode::ArithmeticOverflow)?;
+
+    let stars = &mut coupon.rating_histogram[(rating - MIN_RATING) as usize];
+    *stars = stars.checked_add(1).ok_or(ErrorCode::ArithmeticOverflow)?;
+    coupon.rating_sum = coupon
+        .rating_sum
+        .checked_add(rating as u64)
+        .ok_or(ErrorCode::ArithmeticOverflow)?;
+    coupon.rating_count = coupon
+        .rating_count
+        .checked_add(1)
+        .ok_or(ErrorCode::ArithmeticOverflow)?;
+
+    Ok(())
+}
+
+/// Take a rating back out of the merchant and coupon aggregates
+fn remove_rating(merchant: &mut Merchant, coupon: &mut Coupon, rating: u8) -> Result<()> {
+    merchant.rating_sum = merchant
+        .rating_sum
+        .checked_sub(rating as u64)
+        .ok_or(ErrorCode::ArithmeticOverflow)?;
+    merchant.rating_count = merchant
+        .rating_count
+        .checked_sub(1)
+        .ok_or(ErrorCode::ArithmeticOverflow)?;
+
+    let stars = &mut coupon.rating_histogram[(rating - MIN_RATING) as usize];
+    *stars = stars.checked_sub(1).ok_or(ErrorCode::ArithmeticOverflow)?;
+    coupon.rating_sum = coupon
+        .rating_sum
+        .checked_sub(rating as u64)
+        .ok_or(ErrorCode::ArithmeticOverflow)?;
+    coupon.rating_count = coupon
+        .rating_count
+        .checked_sub(1)
+        .ok_or(ErrorCode::ArithmeticOverflow)?;
+
+    Ok(())
+}
+
+// ============ MERCHANT HELPERS ============
+
+fn set_merchant_verification(
+    ctx: Context<SetMerchantVerification>,
+    is_verified: bool,
+    evidence_hash: [u8; 32],
+) -> Result<()> {
+    require!(evidence_hash != [0; 32], ErrorCode::EvidenceRequired);
+
+    let merchant = &mut ctx.accounts.merchant;
+    let now = Clock::get()?.unix_timestamp;
+
+    merchant.is_verified = is_verified;
+    merchant.verification_evidence = evidence_hash;
+    merchant.verified_by = ctx.accounts.verifier.key();
+    merchant.verification_updated_at = now;
+
+    emit!(MerchantVerificationUpdated {
+        merchant: merchant.key(),
+        is_verified,
+        evidence_hash,
+        verifier: merchant.verified_by,
+        timestamp: now,
+    });
+
+    Ok(())
+}
+
+// ============ CAMPAIGN HELPERS ============
+
+/// The coupon's campaign account, which must be passed when the coupon has one
+fn coupon_campaign<'a, 'info>(
+    coupon: &Coupon,
+    campaign: &'a mut Option<Account<'info, Campaign>>,
+) -> Result<Option<&'a mut Account<'info, Campaign>>> {
+    match coupon.campaign {
+        Some(key) => {
+            let campaign = campaign.as_mut().ok_or(ErrorCode::CampaignRequired)?;
+            require_keys_eq!(campaign.key(), key, ErrorCode::WrongCampaign);
+            Ok(Some(campaign))
+        }
+        None => Ok(None),
+    }
+}
+
+/// Worst-case discount a coupon's supply reserves against its campaign
+/// budget: the full percent off the price plus the fixed discount per unit
+fn committed_discount(
+    price: u64,
+    discount_percent: u8,
+    discount_fixed: u64,
+    supply: u32,
+) -> Result<u64> {
+    price
+        .checked_mul(discount_percent as u64)
+        .map(|percent_off| percent_off / 100)
+        .and_then(|percent_off| percent_off.checked_add(discount_fixed))
+        .and_then(|per_unit| per_unit.checked_mul(supply as u64))
+        .ok_or(error!(ErrorCode::ArithmeticOverflow))
+}
+
+/// Swap a coupon's reservation, refusing any that would overspend the budget
+fn reserve_campaign_budget(campaign: &mut Campaign, previous: u64, next: u64) -> Result<()> {
+    let committed = campaign
+        .budget_committed
+        .checked_sub(previous)
+        .and_then(|committed| committed.checked_add(next))
+        .ok_or(ErrorCode::ArithmeticOverflow)?;
+    require!(
+        next <= previous || committed <= campaign.budget,
+        ErrorCode::CampaignBudgetExceeded
+    );
+    campaign.budget_committed = committed;
+    Ok(())
+}
+
+/// Charge a discount granted at sale time (tier, points, free units), which
+/// no reservation covers, against the campaign budget
+fn charge_campaign_budget(campaign: &mut Campaign, amount: u64) -> Result<()> {
+    reserve_campaign_budget(campaign, 0, amount)
+}
+
+// ============ MATH HELPERS ============
+
+/// Basis-point share of an amount, rounded down
+fn apply_bps(amount: u64, bps: u16) -> Result<u64> {
+    let share = (amount as u128)
+        .checked_mul(bps as u128)
+        .ok_or(ErrorCode::ArithmeticOverflow)?
+        / BPS_DENOMINATOR as u128;
+    u64::try_from(share).map_err(|_| error!(ErrorCode::ArithmeticOverflow))
+}
+
+/// Average a coupon's buyers paid per unit sold, rounded down (free reward
+/// units count as sold, lowering the average rather than adding to it)
+fn average_paid_per_unit(paid_total: u64, units_sold: u32) -> u64 {
+    match units_sold {
+        0 => 0,
+        units => paid_total / units as u64,
+    }
+}
+
+/// Base units of a payment mint covered by `points` at `point_value`
+/// (value of one point in millionths of a whole token)
+fn points_to_base_units(points: u32, point_value: u64, decimals: u8) -> Result<u64> {
+    let value = (points as u128)
+        .checked_mul(point_value as u128)
+        .and_then(|v| v.checked_mul(10u128.checked_pow(decimals as u32)?))
+        .ok_or(ErrorCode::ArithmeticOverflow)?
+        / MICRO_UNITS_PER_TOKEN;
+    u64::try_from(value).map_err(|_| error!(ErrorCode::ArithmeticOverflow))
+}
+
+// ============ LOYALTY HELPERS ============
+
+/// Credit a verified purchase to a loyalty badge
+fn accrue_loyalty(
+    badge: &mut LoyaltyBadge,
+    rules: &LoyaltyRules,
+    multiplier_bps: u16,
+    purchase_amount: u64,
+    decimals: u8,
+    savings_amount: u64,
+) -> Result<()> {
+    // Validate reasonable values
+    require!(purchase_amount <= MAX_PRICE, ErrorCode::InvalidAmount);
+    require!(savings_amount <= MAX_PRICE, ErrorCode::InvalidAmount);
+
+    // Safe updates with overflow protection
+    badge.deals_purchased = badge
+        .deals_purchased
+        .checked_add(1)
+        .ok_or(ErrorCode::ArithmeticOverflow)?;
+
+    badge.total_saved = badge
+        .total_saved
+        .checked_add(savings_amount)
+        .ok_or(ErrorCode::ArithmeticOverflow)?;
+
+    // Points per whole token spent, scaled by the category multiplier
+    let token_unit = 10u128
+        .checked_pow(decimals as u32)
+        .ok_or(ErrorCode::ArithmeticOverflow)?;
+    let new_points = (purchase_amount as u128)
+        .checked_mul(rules.points_per_token as u128)
+        .and_then(|p| p.checked_mul(multiplier_bps as u128))
+        .ok_or(ErrorCode::ArithmeticOverflow)?
+        / token_unit
+        / BPS_DENOMINATOR as u128;
+    let new_points =
+        u32::try_from(new_points).map_err(|_| error!(ErrorCode::ArithmeticOverflow))?;
+    badge.points = badge
+        .points
+        .checked_add(new_points)
+        .ok_or(ErrorCode::ArithmeticOverflow)?;
+    badge.tier_points = badge
+        .tier_points
+        .checked_add(new_points)
+        .ok_or(ErrorCode::ArithmeticOverflow)?;
+
+    // Update tier based on earned points (spending does not demote)
+    badge.tier = tier_for_points(badge.tier_points, &rules.tier_thresholds);
+
+    Ok(())
+}
+
+// ============ BADGE NFT HELPERS ============
+
+fn badge_nft_name(tier: &LoyaltyTier) -> String {
+    format!("DealChain {} Member", tier.name())
+}
+
+fn badge_nft_uri(uri_base: &str, tier: &LoyaltyTier) -> String {
+    format!("{}/{}.json", uri_base, tier.name().to_lowercase())
+}
+
+fn badge_token_metadata(
+    mint: Pubkey,
+    badge: Pubkey,
+    tier: &LoyaltyTier,
+    uri_base: &str,
+) -> TokenMetadata {
+    TokenMetadata {
+        update_authority: Some(badge).try_into().unwrap_or_default(),
+        mint,
+        name: badge_nft_name(tier),
+        symbol: BADGE_SYMBOL.to_string(),
+        uri: badge_nft_uri(uri_base, tier),
+        additional_metadata: vec![(BADGE_TIER_KEY.to_string(), tier.name().to_string())],
+    }
+}
+
+/// Create the Token-2022 non-transferable badge mint with embedded metadata
+/// and mint the single token to the user. The badge PDA is both mint and
+/// metadata update authority.
+fn mint_soulbound_badge(
+    accounts: &InitializeLoyaltyBadge,
+    badge_mint_bump: u8,
+    badge_bump: u8,
+) -> Result<()> {
+    let user_key = accounts.user.key();
+    let mint_key = accounts.badge_mint.key();
+    let badge_key = accounts.loyalty_badge.key();
+    let token_program_id = accounts.token_2022_program.key();
+    let mint_seeds: &[&[u8]] = &[b"badge_mint", user_key.as_ref(), &[badge_mint_bump]];
+    let badge_seeds: &[&[u8]] = &[b"loyalty", user_key.as_ref(), &[badge_bump]];
+
+    let mint_info = accounts.badge_mint.to_account_info();
+    let badge_info = accounts.loyalty_badge.to_account_info();
+
+    // Allocate the mint for its extensions; metadata is appended by Token-2022
+    // later, so rent is prepaid for the longest tier's metadata under the
+    // longest URI base the admin can set
+    let mint_len = ExtensionType::try_calculate_account_len::<spl_token_2022::state::Mint>(&[
+        ExtensionType::NonTransferable,
+        ExtensionType::MetadataPointer,
+    ])?;
+    let longest_uri_base = "x".repeat(MAX_BADGE_URI_BASE_LEN);
+    let metadata_len = badge_token_metadata(
+        mint_key,
+        badge_key,
+        &LoyaltyTier::Platinum,
+        &longest_uri_base,
+    )
+    .tlv_size_of()?;
+    let lamports = Rent::get()?.minimum_balance(mint_len + metadata_len);
+
+    system_program::create_account(
+        CpiContext::new_with_signer(
+            accounts.system_program.to_account_info(),
+            CreateAccount {
+                from: accounts.user.to_account_info(),
+                to: mint_info.clone(),
+            },
+            &[mint_seeds],
+        ),
+        lamports,
+        mint_len as u64,
+        &token_program_id,
+    )?;
+
+    invoke(
+        &spl_token_2022::instruction::initialize_non_transferable_mint(
+            &token_program_id,
+            &mint_key,
+        )?,
+        std::slice::from_ref(&mint_info),
+    )?;
+    invoke(
+        &metadata_pointer::instruction::initialize(
+            &token_program_id,
+            &mint_key,
+            Some(badge_key),
+            Some(mint_key),
+        )?,
+        std::slice::from_ref(&mint_info),
+    )?;
+    invoke(
+        &spl_token_2022::instruction::initialize_mint2(
+            &token_program_id,
+            &mint_key,
+            &badge_key,
+            None,
+            0,
+        )?,
+        std::slice::from_ref(&mint_info),
+    )?;
+
+    let metadata = badge_token_metadata(
+        mint_key,
+        badge_key,
+        &LoyaltyTier::Bronze,
+        &accounts.config.badge_uri_base,
+    );
+    invoke_signed(
+        &spl_token_metadata_interface::instruction::initialize(
+            &token_program_id,
+            &mint_key,
+            &badge_key,
+            &mint_key,
+            &badge_key,
+            metadata.name,
+            metadata.symbol,
+            metadata.uri,
+        ),
+        &[mint_info.clone(), badge_info.clone()],
+        &[badge_seeds],
+    )?;
+    invoke_signed(
+        &spl_token_metadata_interface::instruction::update_field(
+            &token_program_id,
+            &mint_key,
+            &badge_key,
+            Field::Key(BADGE_TIER_KEY.to_string()),
+            LoyaltyTier::Bronze.name().to_string(),
+        ),
+        &[mint_info.clone(), badge_info.clone()],
+        &[badge_seeds],
+    )?;
+
+    associated_token::create(CpiContext::new(
+        accounts.associated_token_program.to_account_info(),
+        associated_token::Create {
+            payer: accounts.user.to_account_info(),
+            associated_token: accounts.user_badge_account.to_account_info(),
+            authority: accounts.user.to_account_info(),
+            mint: mint_info.clone(),
+            system_program: accounts.system_program.to_account_info(),
+            token_program: accounts.token_2022_program.to_account_info(),
+        },
+    ))?;
+
+    token_2022::mint_to(
+        CpiContext::new_with_signer(
+            accounts.token_2022_program.to_account_info(),
+            token_2022::MintTo {
+                mint: mint_info,
+                to: accounts.user_badge_account.to_account_info(),
+                authority: badge_info,
+            },
+            &[badge_seeds],
+        ),
+        1,
+    )
+}
+
+/// Apply decay for every full period since the last one and recompute the tier
+fn refresh_badge(badge: &mut Account<LoyaltyBadge>, config: &GlobalConfig, now: i64) -> Result<()> {
+    let rules = &config.loyalty_rules;
+    if !is_decay_enabled(rules) {
+        // Decay disabled: don't accumulate periods that would apply retroactively
+        badge.last_decay_at = now;
+    } else {
+        let (decay_from, periods) = pending_decay(
+            badge.last_decay_at,
+            config.decay_enabled_at,
+            rules.decay_period,
+            now,
+        );
+        if periods > 0 {
+            let points_before = badge.points;
+            badge.points = decayed_points(badge.points, rules.decay_bps, periods)?;
+            badge.tier_points = decayed_points(badge.tier_points, rules.decay_bps, periods)?;
+            badge.last_decay_at = decay_from
+                .checked_add(periods * rules.decay_period)
+                .ok_or(ErrorCode::ArithmeticOverflow)?;
+
+            emit!(LoyaltyPointsDecayed {
+                badge: badge.key(),
+                user: badge.user,
+                points_expired: points_before - badge.points,
+                timestamp: now,
+            });
+        }
+    }
+
+    // Thresholds may have changed since the last touch
+    badge.tier = tier_for_points(badge.tier_points, &rules.tier_thresholds);
+
+    Ok(())
+}
+
+fn is_decay_enabled(rules: &LoyaltyRules) -> bool {
+    rules.decay_period > 0 && rules.decay_bps > 0
+}
+
+/// Start of a badge's pending decay and the whole periods elapsed since.
+/// Time before decay was switched on never counts.
+fn pending_decay(
+    last_decay_at: i64,
+    decay_enabled_at: i64,
+    decay_period: i64,
+    now: i64,
+) -> (i64, i64) {
+    let decay_from = last_decay_at.max(decay_enabled_at);
+    (decay_from, now.saturating_sub(decay_from) / decay_period)
+}
+
+/// Points left after `periods` decay steps of `decay_bps` each
+fn decayed_points(points: u32, decay_bps: u16, periods: i64) -> Result<u32> {
+    if periods > MAX_DECAY_STEPS {
+        return Ok(0);
+    }
+    let mut points = points;
+    for _ in 0..periods {
+        points -= apply_bps(points as u64, decay_bps)? as u32;
+    }
+    Ok(points)
+}
+
+/// Highest tier whose threshold `tier_points` reaches
+fn tier_for_points(tier_points: u32, thresholds: &[u32; 3]) -> LoyaltyTier {
+    if tier_points >= thresholds[2] {
+        LoyaltyTier::Platinum
+    } else if tier_points >= thresholds[1] {
+        LoyaltyTier::Gold
+    } else if tier_points >= thresholds[0] {
+        LoyaltyTier::Silver
+    } else {
+        LoyaltyTier::Bronze
+    }
+}
+
+// ============ ACCOUNT CONTEXTS ============
+
+#[derive(Accounts)]
+pub struct InitializeConfig<'info> {
+    #[account(
+        init,
+        payer = admin,
+        space = 8 + GlobalConfig::INIT_SPACE,
+        seeds = [b"config"],
+        bump
+    )]
+    pub config: Account<'info, GlobalConfig>,
+
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    /// Only the program's upgrade authority can claim the admin role
+    #[account(
+        constraint = program.programdata_address()? == Some(program_data.key()) @ ErrorCode::Unauthorized
+    )]
+    pub program: Program<'info, crate::program::Dealchain>,
+
+    #[account(
+        constraint = program_data.upgrade_authority_address == Some(admin.key()) @ ErrorCode::Unauthorized
+    )]
+    pub program_data: Account<'info, ProgramData>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateConfig<'info> {
+    #[account(
+        mut,
+        seeds = [b"config"],
+        bump = config.bump,
+        has_one = admin @ ErrorCode::Unauthorized
+    )]
+    pub config: Account<'info, GlobalConfig>,
+
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct QueueConfigChange<'info> {
+    #[account(
+        init,
+        payer = admin,
+        space = 8 + PendingConfigChange::INIT_SPACE,
+        seeds = [b"config_change".as_ref(), &config.next_change_id.to_le_bytes()],
+        bump
+    )]
+    pub pending_change: Account<'info, PendingConfigChange>,
+
+    #[account(
+        mut,
+        seeds = [b"config"],
+        bump = config.bump,
+        has_one = admin @ ErrorCode::Unauthorized
+    )]
+    pub config: Account<'info, GlobalConfig>,
+
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ResolveConfigChange<'info> {
+    #[account(
+        mut,
+        close = admin,
+        seeds = [b"config_change".as_ref(), &pending_change.id.to_le_bytes()],
+        bump = pending_change.bump
+    )]
+    pub pending_change: Account<'info, PendingConfigChange>,
+
+    #[account(
+        mut,
+        seeds = [b"config"],
+        bump = config.bump,
+        has_one = admin @ ErrorCode::Unauthorized
+    )]
+    pub config: Account<'info, GlobalConfig>,
+
+    /// Target of a `CategoryMultiplier` change
+    #[account(
+        mut,
+        seeds = [b"category", category.id.to_le_bytes().as_ref()],
+        bump = category.bump
+    )]
+    pub category: Option<Account<'info, Category>>,
+
+    #[account(mut)]
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CreateCategory<'info> {
+    #[account(
+        init,
+        payer = admin,
+        space = 8 + Category::INIT_SPACE,
+        seeds = [b"category", config.category_count.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub category: Account<'info, Category>,
+
+    #[account(
+        seeds = [b"category", parent.id.to_le_bytes().as_ref()],
+        bump = parent.bump
+    )]
+    pub parent: Option<Account<'info, Category>>,
+
+    #[account(
+        mut,
+        seeds = [b"config"],
+        bump = config.bump,
+        has_one = admin @ ErrorCode::Unauthorized
+    )]
+    pub config: Account<'info, GlobalConfig>,
+
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateCategory<'info> {
+    #[account(
+        mut,
+        seeds = [b"category", category.id.to_le_bytes().as_ref()],
+        bump = category.bump
+    )]
+    pub category: Account<'info, Category>,
+
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        has_one = admin @ ErrorCode::Unauthorized
+    )]
+    pub config: Account<'info, GlobalConfig>,
+
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(name: String, description: String)]
+pub struct InitializeMerchant<'info> {
+    #[account(
+        init,
+        payer = authority,
+        space = Merchant::space(&name, &description, "", ""),
+        seeds = [b"merchant", authority.key().as_ref()],
+        bump
+    )]
+    pub merchant: Account<'info, Merchant>,
+
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        constraint = !config.paused.merchant_operations @ ErrorCode::ProtocolPaused
+    )]
+    pub config: Account<'info, GlobalConfig>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(name: String, description: String, logo_uri: String, website: String)]
+pub struct UpdateMerchantProfile<'info> {
+    #[account(
+        mut,
+        seeds = [b"merchant", merchant.merchant_id.as_ref()],
+        bump = merchant.bump,
+        has_one = authority @ ErrorCode::Unauthorized,
+        constraint = !merchant.is_closed @ ErrorCode::MerchantClosed,
+        realloc = Merchant::space(&name, &description, &logo_uri, &website),
+        realloc::payer = authority,
+        realloc::zero = false
+    )]
+    pub merchant: Account<'info, Merchant>,
+
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        constraint = !config.paused.merchant_operations @ ErrorCode::ProtocolPaused
+    )]
+    pub config: Account<'info, GlobalConfig>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ProposeAuthorityTransfer<'info> {
+    #[account(
+        mut,
+        seeds = [b"merchant", merchant.merchant_id.as_ref()],
+        bump = merchant.bump,
+        has_one = authority @ ErrorCode::Unauthorized
+    )]
+    pub merchant: Account<'info, Merchant>,
+
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        constraint = !config.paused.merchant_operations @ ErrorCode::ProtocolPaused
+    )]
+    pub config: Account<'info, GlobalConfig>,
+
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct AcceptAuthorityTransfer<'info> {
+    #[account(
+        mut,
+        seeds = [b"merchant", merchant.merchant_id.as_ref()],
+        bump = merchant.bump,
+        constraint = merchant.pending_authority == Some(new_authority.key()) @ ErrorCode::Unauthorized
+    )]
+    pub merchant: Account<'info, Merchant>,
+
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        constraint = !config.paused.merchant_operations @ ErrorCode::ProtocolPaused
+    )]
+    pub config: Account<'info, GlobalConfig>,
+
+    pub new_authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CreateCampaign<'info> {
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + Campaign::INIT_SPACE,
+        seeds = [
+            b"campaign",
+            merchant.key().as_ref(),
+            &merchant.total_campaigns.to_le_bytes()
+        ],
+        bump
+    )]
+    pub campaign: Account<'info, Campaign>,
+
+    #[account(
+        mut,
+        seeds = [b"merchant", merchant.merchant_id.as_ref()],
+        bump = merchant.bump,
+        has_one = authority @ ErrorCode::Unauthorized
+    )]
+    pub merchant: Account<'info, Merchant>,
+
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        constraint = !config.paused.merchant_operations @ ErrorCode::ProtocolPaused
+    )]
+    pub config: Account<'info, GlobalConfig>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CreateCoupon<'info> {
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + Coupon::INIT_SPACE,
+        seeds = [b"coupon", mint.key().as_ref()],
+        bump
+    )]
+    pub coupon: Account<'info, Coupon>,
+
+    /// Catalogue entry at the merchant's next coupon index
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + CouponIndexEntry::INIT_SPACE,
+        seeds = [
+            b"coupon",
+            merchant.key().as_ref(),
+            &merchant.total_coupons_created.to_le_bytes()
+        ],
+        bump
+    )]
+    pub coupon_index: Account<'info, CouponIndexEntry>,
+
+    #[account(
+        mut,
+        seeds = [b"merchant", merchant.merchant_id.as_ref()],
+        bump = merchant.bump,
+        has_one = authority @ ErrorCode::Unauthorized
+    )]
+    pub merchant: Account<'info, Merchant>,
+
+    /// Mint must not have freeze authority (security)
+    #[account(
+        constraint = mint.freeze_authority.is_none() @ ErrorCode::MintMustNotHaveFreezeAuthority
+    )]
+    pub mint: Account<'info, Mint>,
+
+    /// Mint buyers will pay in
+    pub payment_mint: Account<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [b"category", category.id.to_le_bytes().as_ref()],
+        bump = category.bump,
+        constraint = category.is_active @ ErrorCode::CategoryInactive
+    )]
+    pub category: Account<'info, Category>,
+
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        constraint = !config.paused.merchant_operations @ ErrorCode::ProtocolPaused
+    )]
+    pub config: Account<'info, GlobalConfig>,
+
+    /// Campaign to create the coupon under
+    #[account(
+        mut,
+        seeds = [b"campaign", merchant.key().as_ref(), &campaign.id.to_le_bytes()],
+        bump = campaign.bump,
+        has_one = merchant @ ErrorCode::Unauthorized
+    )]
+    pub campaign: Option<Account<'info, Campaign>>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct PurchaseCoupon<'info> {
+    #[account(
+        mut,
+        seeds = [b"coupon", coupon.mint.as_ref()],
+        bump = coupon.bump,
+        // Optimistic locking to prevent race conditions
+        constraint = coupon.total_purchases < coupon.max_redemptions @ ErrorCode::SoldOut,
+        constraint = coupon.merchant == merchant.key() @ ErrorCode::Unauthorized
+    )]
+    pub coupon: Account<'info, Coupon>,
+
+    #[account(
+        mut,
+        seeds = [b"merchant", merchant.merchant_id.as_ref()],
+        bump = merchant.bump
+    )]
+    pub merchant: Account<'info, Merchant>,
+
+    #[account(mut)]
+    pub buyer: Signer<'info>,
+
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = buyer
+    )]
+    pub buyer_token_account: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = merchant.authority
+    )]
+    pub merchant_token_account: Account<'info, TokenAccount>,
+
+    #[account(
+        address = coupon.payment_mint @ ErrorCode::WrongPaymentMint
+    )]
+    pub mint: Account<'info, Mint>,
+
+    /// Coupon's category (counters and loyalty multiplier)
+    #[account(
+        mut,
+        address = coupon.category @ ErrorCode::WrongCategory
+    )]
+    pub category: Account<'info, Category>,
+
+    /// Buyer's loyalty badge (required for tier-gated deals, credited on purchase)
+    #[account(
+        mut,
+        seeds = [b"loyalty", buyer.key().as_ref()],
+        bump = buyer_loyalty_badge.bump
+    )]
+    pub buyer_loyalty_badge: Option<Account<'info, LoyaltyBadge>>,
+
+    /// Buyer's balance of the gate mint (required for token-gated deals)
+    #[account(
+        constraint = buyer_gate_token_account.owner == buyer.key() @ ErrorCode::Unauthorized
+    )]
+    pub buyer_gate_token_account: Option<Account<'info, TokenAccount>>,
+
+    /// Buyer's NFT from the gate collection (required for collection-gated deals)
+    #[account(
+        constraint = buyer_collection_nft_account.owner == buyer.key() @ ErrorCode::Unauthorized
+    )]
+    pub buyer_collection_nft_account: Option<Account<'info, TokenAccount>>,
+
+    /// CHECK: Metaplex metadata of the buyer's collection NFT; owner, address
+    /// and collection are verified in purchase_coupon
+    pub buyer_collection_nft_metadata: Option<UncheckedAccount<'info>>,
+
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        constraint = !config.paused.purchases @ ErrorCode::ProtocolPaused
+    )]
+    pub config: Account<'info, GlobalConfig>,
+
+    /// Platform treasury (config-owned), required when points are treasury-funded
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = config
+    )]
+    pub treasury_token_account: Option<Account<'info, TokenAccount>>,
+
+    /// Campaign the coupon belongs to (required when the coupon has one)
+    #[account(
+        mut,
+        seeds = [b"campaign", merchant.key().as_ref(), &campaign.id.to_le_bytes()],
+        bump = campaign.bump
+    )]
+    pub campaign: Option<Account<'info, Campaign>>,
+
+    pub token_program: Program<'info, Token>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RedeemCoupon<'info> {
+    #[account(
+        mut,
+        seeds = [b"coupon", coupon.mint.as_ref()],
+        bump = coupon.bump,
+        constraint = coupon.merchant == merchant.key() @ ErrorCode::Unauthorized
+    )]
+    pub coupon: Account<'info, Coupon>,
+
+    #[account(
+        mut,
+        seeds = [b"merchant", merchant.merchant_id.as_ref()],
+        bump = merchant.bump
+    )]
+    pub merchant: Account<'info, Merchant>,
+
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        constraint = !config.paused.redemptions @ ErrorCode::ProtocolPaused
+    )]
+    pub config: Account<'info, GlobalConfig>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    #[account(
+        mut,
+        constraint = user_nft_account.mint == coupon.mint @ ErrorCode::WrongNFT,
+        constraint = user_nft_account.owner == user.key() @ ErrorCode::UserDoesNotOwnNFT,
+        constraint = user_nft_account.amount == 1 @ ErrorCode::InvalidNFTAmount
+    )]
+    pub user_nft_account: Account<'info, TokenAccount>,
+
+    /// CRITICAL: Prevents duplicate redemptions (PDA ensures uniqueness)
+    #[account(
+        init,
+        payer = user,
+        space = 8 + RedemptionRecord::INIT_SPACE,
+        seeds = [b"redemption", coupon.key().as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub redemption_record: Account<'info, RedemptionRecord>,
+
+    /// CHECK: Redeemer's review PDA for this coupon, which may not exist yet;
+    /// a held-unit review found here is moved onto the redemption
+    #[account(
+        mut,
+        seeds = [b"review", coupon.key().as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub review: UncheckedAccount<'info>,
+
+    pub mint: Account<'info, Mint>,
+
+    /// Coupon's category (counters and loyalty multiplier)
+    #[account(
+        mut,
+        address = coupon.category @ ErrorCode::WrongCategory
+    )]
+    pub category: Account<'info, Category>,
+
+    /// Merchant's stamp program (stamps are only added when both are passed)
+    #[account(
+        seeds = [b"loyalty_program", merchant.key().as_ref()],
+        bump = loyalty_program.bump
+    )]
+    pub loyalty_program: Option<Account<'info, MerchantLoyaltyProgram>>,
+
+    #[account(
+        mut,
+        seeds = [b"stamp_card", merchant.key().as_ref(), user.key().as_ref()],
+        bump = stamp_card.bump
+    )]
+    pub stamp_card: Option<Account<'info, StampCard>>,
+
+    /// User's achievement progress (updated when passed)
+    #[account(
+        mut,
+        seeds = [b"achievements", user.key().as_ref()],
+        bump = achievements.bump
+    )]
+    pub achievements: Option<Account<'info, Achievements>>,
+
+    /// Campaign the coupon belongs to (required when the coupon has one)
+    #[account(
+        mut,
+        seeds = [b"campaign", merchant.key().as_ref(), &campaign.id.to_le_bytes()],
+        bump = campaign.bump
+    )]
+    pub campaign: Option<Account<'info, Campaign>>,
+
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SubmitReview<'info> {
+    #[account(
+        init,
+        payer = user,
+        space = 8 + Review::INIT_SPACE,
+        seeds = [b"review", coupon.key().as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub review: Account<'info, Review>,
+
+    #[account(
+        mut,
+        seeds = [b"coupon", coupon.mint.as_ref()],
+        bump = coupon.bump,
+        constraint = coupon.merchant == merchant.key() @ ErrorCode::Unauthorized
+    )]
+    pub coupon: Account<'info, Coupon>,
+
+    #[account(
+        mut,
+        seeds = [b"merchant", merchant.merchant_id.as_ref()],
+        bump = merchant.bump
+    )]
+    pub merchant: Account<'info, Merchant>,
+
+    /// User's NFT account (proof for holders who have not redeemed)
+    #[account(
+        constraint = user_nft_account.mint == coupon.mint @ ErrorCode::WrongNFT,
+        constraint = user_nft_account.owner == user.key() @ ErrorCode::UserDoesNotOwnNFT
+    )]
+    pub user_nft_account: Option<Account<'info, TokenAccount>>,
+
+    /// Redemption record (proof once the NFT has been burned)
+    #[account(
+        seeds = [b"redemption", coupon.key().as_ref(), user.key().as_ref()],
+        bump = redemption_record.bump
+    )]
+    pub redemption_record: Option<Account<'info, RedemptionRecord>>,
+
+    /// User's achievement progress (updated when passed)
+    #[account(
+        mut,
+        seeds = [b"achievements", user.key().as_ref()],
+        bump = achievements.bump
+    )]
+    pub achievements: Option<Account<'info, Achievements>>,
+
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        constraint = !config.paused.reviews @ ErrorCode::ProtocolPaused
+    )]
+    pub config: Account<'info, GlobalConfig>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct EditReview<'info> {
+    #[account(
+        mut,
+        seeds = [b"review", review.coupon.as_ref(), user.key().as_ref()],
+        bump = review.bump,
+        has_one = user @ ErrorCode::Unauthorized
+    )]
+    pub review: Account<'info, Review>,
+
+    #[account(
+        mut,
+        seeds = [b"coupon", coupon.mint.as_ref()],
+        bump = coupon.bump,
+        address = review.coupon @ ErrorCode::Unauthorized,
+        constraint = coupon.merchant == merchant.key() @ ErrorCode::Unauthorized
+    )]
+    pub coupon: Account<'info, Coupon>,
+
+    #[account(
+        mut,
+        seeds = [b"merchant", merchant.merchant_id.as_ref()],
+        bump = merchant.bump
+    )]
+    pub merchant: Account<'info, Merchant>,
+
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        constraint = !config.paused.reviews @ ErrorCode::ProtocolPaused
+    )]
+    pub config: Account<'info, GlobalConfig>,
+
+    pub user: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct DeleteReview<'info> {
+    #[account(
+        mut,
+        close = user,
+        seeds = [b"review", review.coupon.as_ref(), user.key().as_ref()],
+        bump = review.bump,
+        has_one = user @ ErrorCode::Unauthorized
+    )]
+    pub review: Account<'info, Review>,
+
+    #[account(
+        mut,
+        seeds = [b"coupon", coupon.mint.as_ref()],
+        bump = coupon.bump,
+        address = review.coupon @ ErrorCode::Unauthorized,
+        constraint = coupon.merchant == merchant.key() @ ErrorCode::Unauthorized
+    )]
+    pub coupon: Account<'info, Coupon>,
+
+    #[account(
+        mut,
+        seeds = [b"merchant", merchant.merchant_id.as_ref()],
+        bump = merchant.bump
+    )]
+    pub merchant: Account<'info, Merchant>,
+
+    /// User's achievement progress (required when the review counted toward it)
+    #[account(
+        mut,
+        seeds = [b"achievements", user.key().as_ref()],
+        bump = achievements.bump
+    )]
+    pub achievements: Option<Account<'info, Achievements>>,
+
+    /// Merchant's reply to the review (required when there is one)
+    #[account(
+        mut,
+        seeds = [b"review_reply", review.key().as_ref()],
+        bump = reply.bump
+    )]
+    pub reply: Option<Account<'info, ReviewReply>>,
+
+    /// Whoever wrote the reply, refunded its rent when it's closed
+    #[account(mut)]
+    pub reply_author: Option<SystemAccount<'info>>,
+
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        constraint = !config.paused.reviews @ ErrorCode::ProtocolPaused
+    )]
+    pub config: Account<'info, GlobalConfig>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeFlagger<'info> {
+    #[account(
+        init,
+        payer = flagger,
+        space = 8 + FlaggerState::INIT_SPACE,
+        seeds = [b"flagger", flagger.key().as_ref()],
+        bump
+    )]
+    pub flagger_state: Account<'info, FlaggerState>,
+
+    #[account(mut)]
+    pub flagger: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct FlagReview<'info> {
+    /// One flag per flagger and review
+    #[account(
+        init,
+        payer = flagger,
+        space = 8 + ReviewFlag::INIT_SPACE,
+        seeds = [b"review_flag", review.key().as_ref(), flagger.key().as_ref()],
+        bump
+    )]
+    pub flag: Account<'info, ReviewFlag>,
+
+    #[account(
+        mut,
+        seeds = [b"review", review.coupon.as_ref(), review.user.as_ref()],
+        bump = review.bump
+    )]
+    pub review: Account<'info, Review>,
+
+    #[account(
+        mut,
+        seeds = [b"flagger", flagger.key().as_ref()],
+        bump = flagger_state.bump
+    )]
+    pub flagger_state: Account<'info, FlaggerState>,
+
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        constraint = !config.paused.reviews @ ErrorCode::ProtocolPaused
+    )]
+    pub config: Account<'info, GlobalConfig>,
+
+    #[account(mut)]
+    pub flagger: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ModerateReview<'info> {
+    #[account(
+        mut,
+        seeds = [b"review", review.coupon.as_ref(), review.user.as_ref()],
+        bump = review.bump
+    )]
+    pub review: Account<'info, Review>,
+
+    #[account(
+        mut,
+        seeds = [b"coupon", coupon.mint.as_ref()],
+        bump = coupon.bump,
+        address = review.coupon @ ErrorCode::Unauthorized,
+        constraint = coupon.merchant == merchant.key() @ ErrorCode::Unauthorized
+    )]
+    pub coupon: Account<'info, Coupon>,
+
+    #[account(
+        mut,
+        seeds = [b"merchant", merchant.merchant_id.as_ref()],
+        bump = merchant.bump
+    )]
+    pub merchant: Account<'info, Merchant>,
+
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        constraint = config.admin == moderator.key()
+            || config.moderators.contains(&moderator.key()) @ ErrorCode::Unauthorized
+    )]
+    pub config: Account<'info, GlobalConfig>,
+
+    pub moderator: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ReplyToReview<'info> {
+    #[account(
+        init,
+        payer = author,
+        space = 8 + ReviewReply::INIT_SPACE,
+        seeds = [b"review_reply", review.key().as_ref()],
+        bump
+    )]
+    pub reply: Account<'info, ReviewReply>,
+
+    #[account(
+        mut,
+        seeds = [b"review", review.coupon.as_ref(), review.user.as_ref()],
+        bump = review.bump
+    )]
+    pub review: Account<'info, Review>,
+
+    #[account(
+        seeds = [b"coupon", coupon.mint.as_ref()],
+        bump = coupon.bump,
+        address = review.coupon @ ErrorCode::Unauthorized,
+        constraint = coupon.merchant == merchant.key() @ ErrorCode::Unauthorized
+    )]
+    pub coupon: Account<'info, Coupon>,
+
+    #[account(
+        seeds = [b"merchant", merchant.merchant_id.as_ref()],
+        bump = merchant.bump,
+        constraint = merchant.authority == author.key()
+            || merchant.staff.contains(&author.key()) @ ErrorCode::Unauthorized
+    )]
+    pub merchant: Account<'info, Merchant>,
+
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        constraint = !config.paused.reviews @ ErrorCode::ProtocolPaused
+    )]
+    pub config: Account<'info, GlobalConfig>,
+
+    #[account(mut)]
+    pub author: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateCoupon<'info> {
+    #[account(
+        mut,
+        seeds = [b"coupon", coupon.mint.as_ref()],
+        bump = coupon.bump,
+        constraint = coupon.merchant == merchant.key() @ ErrorCode::Unauthorized,
+        constraint = !coupon.is_closed @ ErrorCode::CouponClosed
+    )]
+    pub coupon: Account<'info, Coupon>,
+
+    #[account(
+        seeds = [b"merchant", merchant.merchant_id.as_ref()],
+        bump = merchant.bump,
+        has_one = authority @ ErrorCode::Unauthorized
+    )]
+    pub merchant: Account<'info, Merchant>,
+
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        constraint = !config.paused.merchant_operations @ ErrorCode::ProtocolPaused
+    )]
+    pub config: Account<'info, GlobalConfig>,
+
+    /// Category named by `CouponUpdate::category`
+    #[account(
+        mut,
+        seeds = [b"category", new_category.id.to_le_bytes().as_ref()],
+        bump = new_category.bump,
+        constraint = new_category.is_active @ ErrorCode::CategoryInactive
+    )]
+    pub new_category: Option<Account<'info, Category>>,
+
+    /// Coupon's current category (required with `new_category`)
+    #[account(
+        mut,
+        address = coupon.category @ ErrorCode::WrongCategory
+    )]
+    pub old_category: Option<Account<'info, Category>>,
+
+    /// Campaign the coupon belongs to (required when the coupon has one)
+    #[account(
+        mut,
+        seeds = [b"campaign", merchant.key().as_ref(), &campaign.id.to_le_bytes()],
+        bump = campaign.bump
+    )]
+    pub campaign: Option<Account<'info, Campaign>>,
+
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct RestockCoupon<'info> {
+    #[account(
+        mut,
+        seeds = [b"coupon", coupon.mint.as_ref()],
+        bump = coupon.bump,
+        constraint = coupon.merchant == merchant.key() @ ErrorCode::Unauthorized,
+        constraint = !coupon.is_closed @ ErrorCode::CouponClosed
+    )]
+    pub coupon: Account<'info, Coupon>,
+
+    #[account(
+        seeds = [b"merchant", merchant.merchant_id.as_ref()],
+        bump = merchant.bump,
+        has_one = authority @ ErrorCode::Unauthorized
+    )]
+    pub merchant: Account<'info, Merchant>,
+
+    /// Campaign the coupon belongs to (required when the coupon has one)
+    #[account(
+        mut,
+        seeds = [b"campaign", merchant.key().as_ref(), &campaign.id.to_le_bytes()],
+        bump = campaign.bump
+    )]
+    pub campaign: Option<Account<'info, Campaign>>,
+
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        constraint = !config.paused.merchant_operations @ ErrorCode::ProtocolPaused
+    )]
+    pub config: Account<'info, GlobalConfig>,
+
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateCouponStatus<'info> {
+    #[account(
+        mut,
+        seeds = [b"coupon", coupon.mint.as_ref()],
+        bump = coupon.bump,
+        constraint = coupon.merchant == merchant.key() @ ErrorCode::Unauthorized,
+        constraint = !coupon.is_closed @ ErrorCode::CouponClosed
+    )]
+    pub coupon: Account<'info, Coupon>,
+
+    #[account(
+        seeds = [b"merchant", merchant.merchant_id.as_ref()],
+        bump = merchant.bump,
+        has_one = authority @ ErrorCode::Unauthorized
+    )]
+    pub merchant: Account<'info, Merchant>,
+
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        constraint = !config.paused.merchant_operations @ ErrorCode::ProtocolPaused
+    )]
+    pub config: Account<'info, GlobalConfig>,
+
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ToggleMerchantPause<'info> {
+    #[account(
+        mut,
+        seeds = [b"merchant", merchant.merchant_id.as_ref()],
+        bump = merchant.bump,
+        has_one = authority @ ErrorCode::Unauthorized
+    )]
+    pub merchant: Account<'info, Merchant>,
+
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct BeginMerchantWindDown<'info> {
+    #[account(
+        mut,
+        seeds = [b"merchant", merchant.merchant_id.as_ref()],
+        bump = merchant.bump,
+        has_one = authority @ ErrorCode::Unauthorized
+    )]
+    pub merchant: Account<'info, Merchant>,
+
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        constraint = !config.paused.merchant_operations @ ErrorCode::ProtocolPaused
+    )]
+    pub config: Account<'info, GlobalConfig>,
+
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CloseMerchant<'info> {
+    #[account(
+        mut,
+        seeds = [b"merchant", merchant.merchant_id.as_ref()],
+        bump = merchant.bump,
+        has_one = authority @ ErrorCode::Unauthorized,
+        realloc = Merchant::space(&merchant.name, "", "", ""),
+        realloc::payer = authority,
+        realloc::zero = false
+    )]
+    pub merchant: Account<'info, Merchant>,
+
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        constraint = !config.paused.merchant_operations @ ErrorCode::ProtocolPaused
+    )]
+    pub config: Account<'info, GlobalConfig>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateMerchantStaff<'info> {
+    #[account(
+        mut,
+        seeds = [b"merchant", merchant.merchant_id.as_ref()],
+        bump = merchant.bump,
+        has_one = authority @ ErrorCode::Unauthorized
+    )]
+    pub merchant: Account<'info, Merchant>,
+
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        constraint = !config.paused.merchant_operations @ ErrorCode::ProtocolPaused
+    )]
+    pub config: Account<'info, GlobalConfig>,
+
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetTierDiscounts<'info> {
+    #[account(
+        mut,
+        seeds = [b"merchant", merchant.merchant_id.as_ref()],
+        bump = merchant.bump,
+        has_one = authority @ ErrorCode::Unauthorized
+    )]
+    pub merchant: Account<'info, Merchant>,
+
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        constraint = !config.paused.merchant_operations @ ErrorCode::ProtocolPaused
+    )]
+    pub config: Account<'info, GlobalConfig>,
+
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CreateLoyaltyProgram<'info> {
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + MerchantLoyaltyProgram::INIT_SPACE,
+        seeds = [b"loyalty_program", merchant.key().as_ref()],
+        bump
+    )]
+    pub loyalty_program: Account<'info, MerchantLoyaltyProgram>,
+
+    #[account(
+        seeds = [b"merchant", merchant.merchant_id.as_ref()],
+        bump = merchant.bump,
+        has_one = authority @ ErrorCode::Unauthorized
+    )]
+    pub merchant: Account<'info, Merchant>,
+
+    /// Coupon handed out as the reward (must belong to this merchant)
+    #[account(
+        seeds = [b"coupon", reward_coupon.mint.as_ref()],
+        bump = reward_coupon.bump,
+        constraint = reward_coupon.merchant == merchant.key() @ ErrorCode::Unauthorized
+    )]
+    pub reward_coupon: Account<'info, Coupon>,
+
+    /// Program PDA must be able to mint reward units
+    #[account(
+        address = reward_coupon.mint,
+        constraint = reward_mint.mint_authority == COption::Some(loyalty_program.key()) @ ErrorCode::InvalidRewardMintAuthority
+    )]
+    pub reward_mint: Account<'info, Mint>,
+
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        constraint = !config.paused.merchant_operations @ ErrorCode::ProtocolPaused
+    )]
+    pub config: Account<'info, GlobalConfig>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateLoyaltyProgram<'info> {
+    #[account(
+        mut,
+        seeds = [b"loyalty_program", merchant.key().as_ref()],
+        bump = loyalty_program.bump
+    )]
+    pub loyalty_program: Account<'info, MerchantLoyaltyProgram>,
+
+    #[account(
+        seeds = [b"merchant", merchant.merchant_id.as_ref()],
+        bump = merchant.bump,
+        has_one = authority @ ErrorCode::Unauthorized
+    )]
+    pub merchant: Account<'info, Merchant>,
+
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        constraint = !config.paused.merchant_operations @ ErrorCode::ProtocolPaused
+    )]
+    pub config: Account<'info, GlobalConfig>,
+
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeStampCard<'info> {
+    #[account(
+        init,
+        payer = customer,
+        space = 8 + StampCard::INIT_SPACE,
+        seeds = [b"stamp_card", merchant.key().as_ref(), customer.key().as_ref()],
+        bump
+    )]
+    pub stamp_card: Account<'info, StampCard>,
+
+    #[account(
+        seeds = [b"merchant", merchant.merchant_id.as_ref()],
+        bump = merchant.bump
+    )]
+    pub merchant: Account<'info, Merchant>,
+
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        constraint = !config.paused.loyalty @ ErrorCode::ProtocolPaused
+    )]
+    pub config: Account<'info, GlobalConfig>,
+
+    #[account(mut)]
+    pub customer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimStampReward<'info> {
+    #[account(
+        mut,
+        seeds = [b"stamp_card", merchant.key().as_ref(), customer.key().as_ref()],
+        bump = stamp_card.bump
+    )]
+    pub stamp_card: Account<'info, StampCard>,
+
+    #[account(
+        mut,
+        seeds = [b"loyalty_program", merchant.key().as_ref()],
+        bump = loyalty_program.bump
+    )]
+    pub loyalty_program: Account<'info, MerchantLoyaltyProgram>,
+
+    #[account(
+        seeds = [b"merchant", merchant.merchant_id.as_ref()],
+        bump = merchant.bump
+    )]
+    pub merchant: Account<'info, Merchant>,
+
+    #[account(
+        mut,
+        address = loyalty_program.reward_coupon @ ErrorCode::Unauthorized
+    )]
+    pub reward_coupon: Account<'info, Coupon>,
+
+    #[account(
+        mut,
+        address = reward_coupon.mint
+    )]
+    pub reward_mint: Account<'info, Mint>,
+
+    #[account(
+        mut,
+        associated_token::mint = reward_mint,
+        associated_token::authority = customer
+    )]
+    pub customer_reward_account: Account<'info, TokenAccount>,
+
+    /// Campaign the reward coupon belongs to (required when it has one)
+    #[account(
+        mut,
+        seeds = [b"campaign", merchant.key().as_ref(), &campaign.id.to_le_bytes()],
+        bump = campaign.bump
+    )]
+    pub campaign: Option<Account<'info, Campaign>>,
+
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        constraint = !config.paused.loyalty @ ErrorCode::ProtocolPaused
+    )]
+    pub config: Account<'info, GlobalConfig>,
+
+    pub customer: Signer<'info>,
+
+    pub token_program: Program<'info, Token>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeLoyaltyBadge<'info> {
+    #[account(
+        init,
+        payer = user,
+        space = 8 + LoyaltyBadge::INIT_SPACE,
+        seeds = [b"loyalty", user.key().as_ref()],
+        bump
+    )]
+    pub loyalty_badge: Account<'info, LoyaltyBadge>,
+
+    /// CHECK: Soulbound badge mint, created and initialized in the handler
+    #[account(
+        mut,
+        seeds = [b"badge_mint", user.key().as_ref()],
+        bump
+    )]
+    pub badge_mint: UncheckedAccount<'info>,
+
+    /// CHECK: User's Token-2022 ATA for the badge mint; the ATA program
+    /// verifies the address when creating it
+    #[account(mut)]
+    pub user_badge_account: UncheckedAccount<'info>,
+
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        constraint = !config.paused.loyalty @ ErrorCode::ProtocolPaused
+    )]
+    pub config: Account<'info, GlobalConfig>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    pub token_2022_program: Program<'info, Token2022>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SyncLoyaltyBadgeNft<'info> {
+    #[account(
+        mut,
+        seeds = [b"loyalty", loyalty_badge.user.as_ref()],
+        bump = loyalty_badge.bump
+    )]
+    pub loyalty_badge: Account<'info, LoyaltyBadge>,
+
+    /// CHECK: Badge mint recorded on the loyalty badge (metadata lives in the mint)
+    #[account(
+        mut,
+        address = loyalty_badge.badge_mint
+    )]
+    pub badge_mint: UncheckedAccount<'info>,
+
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        constraint = !config.paused.loyalty @ ErrorCode::ProtocolPaused
+    )]
+    pub config: Account<'info, GlobalConfig>,
+
+    pub token_2022_program: Program<'info, Token2022>,
+}
+
+#[derive(Accounts)]
+pub struct SetMerchantVerification<'info> {
+    #[account(
+        mut,
+        seeds = [b"merchant", merchant.merchant_id.as_ref()],
+        bump = merchant.bump
+    )]
+    pub merchant: Account<'info, Merchant>,
+
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        constraint = config.verifiers.contains(&verifier.key()) @ ErrorCode::Unauthorized
+    )]
+    pub config: Account<'info, GlobalConfig>,
+
+    pub verifier: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CreateAchievement<'info> {
+    #[account(
+        init,
+        payer = admin,
+        space = 8 + AchievementDefinition::INIT_SPACE,
+        seeds = [b"achievement", config.achievement_count.to_le_bytes().as_ref()],
+        bump,
+        constraint = config.achievement_count < MAX_ACHIEVEMENTS @ ErrorCode::TooManyAchievements
+    )]
+    pub achievement: Account<'info, AchievementDefinition>,
+
+    #[account(
+        mut,
+        seeds = [b"config"],
+        bump = config.bump,
+        has_one = admin @ ErrorCode::Unauthorized
+    )]
+    pub config: Account<'info, GlobalConfig>,
+
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SetAchievementActive<'info> {
+    #[account(
+        mut,
+        seeds = [b"achievement", achievement.id.to_le_bytes().as_ref()],
+        bump = achievement.bump
+    )]
+    pub achievement: Account<'info, AchievementDefinition>,
+
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        has_one = admin @ ErrorCode::Unauthorized
+    )]
+    pub config: Account<'info, GlobalConfig>,
+
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeAchievements<'info> {
+    #[account(
+        init,
+        payer = user,
+        space = 8 + Achievements::INIT_SPACE,
+        seeds = [b"achievements", user.key().as_ref()],
+        bump
+    )]
+    pub achievements: Account<'info, Achievements>,
+
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        constraint = !config.paused.loyalty @ ErrorCode::ProtocolPaused
+    )]
+    pub config: Account<'info, GlobalConfig>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimAchievement<'info> {
+    #[account(
+        seeds = [b"achievement", achievement.id.to_le_bytes().as_ref()],
+        bump = achievement.bump
+    )]
+    pub achievement: Account<'info, AchievementDefinition>,
+
+    #[account(
+        mut,
+        seeds = [b"achievements", user.key().as_ref()],
+        bump = achievements.bump
+    )]
+    pub achievements: Account<'info, Achievements>,
+
+    #[account(
+        mut,
+        seeds = [b"loyalty", user.key().as_ref()],
+        bump = loyalty_badge.bump
+    )]
+    pub loyalty_badge: Account<'info, LoyaltyBadge>,
+
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        constraint = !config.paused.loyalty @ ErrorCode::ProtocolPaused
+    )]
+    pub config: Account<'info, GlobalConfig>,
+
+    pub user: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct RefreshLoyaltyBadge<'info> {
+    #[account(
+        mut,
+        seeds = [b"loyalty", loyalty_badge.user.as_ref()],
+        bump = loyalty_badge.bump
+    )]
+    pub loyalty_badge: Account<'info, LoyaltyBadge>,
+
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        constraint = !config.paused.loyalty @ ErrorCode::ProtocolPaused
+    )]
+    pub config: Account<'info, GlobalConfig>,
+}
+
+#[derive(Accounts)]
+pub struct CloseExpiredCoupon<'info> {
+    /// Shrunk to a tombstone rather than closed
+    #[account(
+        mut,
+        seeds = [b"coupon", coupon.mint.as_ref()],
         bump = coupon.bump,
-        // Optimistic locking to prevent race conditions
-        constraint = coupon.total_purchases < coupon.max_redemptions @ ErrorCode::SoldOut
+        constraint = coupon.merchant == merchant.key() @ ErrorCode::Unauthorized,
+        constraint = !coupon.is_closed @ ErrorCode::CouponClosed,
+        realloc = Coupon::TOMBSTONE_SPACE,
+        realloc::payer = merchant_authority,
+        realloc::zero = false
     )]
     pub coupon: Account<'info, Coupon>,
 
     #[account(
         mut,
-        seeds = [b"merchant", merchant.authority.as_ref()],
-        bump = merchant.bump
+        seeds = [b"merchant", merchant.merchant_id.as_ref()],
+        bump = merchant.bump,
+        constraint = merchant.authority == merchant_authority.key() @ ErrorCode::Unauthorized
     )]
     pub merchant: Account<'info, Merchant>,
 
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        constraint = !config.paused.merchant_operations @ ErrorCode::ProtocolPaused
+    )]
+    pub config: Account<'info, GlobalConfig>,
+
+    /// Coupon's category (loses the coupon from its count)
+    #[account(
+        mut,
+        address = coupon.category @ ErrorCode::WrongCategory
+    )]
+    pub category: Account<'info, Category>,
+
     #[account(mut)]
-    pub buyer: Signer<'info>,
+    pub merchant_authority: Signer<'info>,
 
+    /// Mint holders paid in; refunds are escrowed in it
+    #[account(
+        address = coupon.payment_mint @ ErrorCode::WrongPaymentMint
+    )]
+    pub payment_mint: Account<'info, Mint>,
+
+    /// Funds the refunds (required when units are outstanding)
     #[account(
         mut,
-        associated_token::mint = mint,
-        associated_token::authority = buyer,
-        constraint = buyer_token_account.amount >= coupon.price @ ErrorCode::InsufficientFunds
+        associated_token::mint = payment_mint,
+        associated_token::authority = merchant_authority
     )]
-    pub buyer_token_account: Account<'info, TokenAccount>,
+    pub merchant_token_account: Option<Account<'info, TokenAccount>>,
+
+    /// Refund escrow owned by the coupon PDA (required when units are outstanding)
+    #[account(
+        init,
+        payer = merchant_authority,
+        token::mint = payment_mint,
+        token::authority = coupon,
+        seeds = [b"refund_vault", coupon.key().as_ref()],
+        bump
+    )]
+    pub refund_vault: Option<Account<'info, TokenAccount>>,
+
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
 
+#[derive(Accounts)]
+pub struct ClaimRefund<'info> {
     #[account(
         mut,
-        associated_token::mint = mint,
-        associated_token::authority = merchant.authority
+        seeds = [b"coupon", coupon.mint.as_ref()],
+        bump = coupon.bump,
+        constraint = coupon.is_closed @ ErrorCode::CouponNotClosed
     )]
-    pub merchant_token_account: Account<'info, TokenAccount>,
+    pub coupon: Account<'info, Coupon>,
+
+    #[account(
+        mut,
+        address = coupon.mint @ ErrorCode::WrongNFT
+    )]
+    pub mint: Account<'info, Mint>,
+
+    #[account(
+        mut,
+        constraint = holder_nft_account.mint == coupon.mint @ ErrorCode::WrongNFT,
+        constraint = holder_nft_account.owner == holder.key() @ ErrorCode::UserDoesNotOwnNFT,
+        constraint = holder_nft_account.amount == 1 @ ErrorCode::InvalidNFTAmount
+    )]
+    pub holder_nft_account: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"refund_vault", coupon.key().as_ref()],
+        bump
+    )]
+    pub refund_vault: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        constraint = holder_token_account.mint == coupon.payment_mint @ ErrorCode::WrongRefundMint,
+        constraint = holder_token_account.owner == holder.key() @ ErrorCode::Unauthorized
+    )]
+    pub holder_token_account: Account<'info, TokenAccount>,
+
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        constraint = !config.paused.redemptions @ ErrorCode::ProtocolPaused
+    )]
+    pub config: Account<'info, GlobalConfig>,
+
+    pub holder: Signer<'info>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+// ============ STATE ACCOUNTS ============
+
+/// Platform-wide settings (single PDA)
+#[account]
+#[derive(InitSpace)]
+pub struct GlobalConfig {
+    pub admin: Pubkey,
+    pub point_value: u64, // Value of one loyalty point in millionths of a token
+    pub points_funding: PointsFunding,
+    pub loyalty_rules: LoyaltyRules,
+    pub achievement_count: u16,
+    #[max_len(10)]
+    pub verifiers: Vec<Pubkey>, // May call verify_merchant / revoke_verification
+    #[max_len(10)]
+    pub moderators: Vec<Pubkey>, // May hide and restore reviews
+    pub unverified_max_price: u64, // 0 = unverified merchants are not capped
+    pub paused: PauseFlags,
+    pub next_change_id: u64,   // PDA seed of the next queued config change
+    pub category_count: u32,   // PDA seed of the next category
+    pub decay_enabled_at: i64, // When point decay was last switched on
+    #[max_len(128)]
+    pub badge_uri_base: String, // Badge NFT metadata lives at <base>/<tier>.json
+    pub badge_uri_version: u32, // Bumped whenever the base changes
+    pub bump: u8,
+}
+
+/// Admin-managed coupon category (marketplace filters and point multipliers)
+#[account]
+#[derive(InitSpace)]
+pub struct Category {
+    pub id: u32, // PDA seed
+    #[max_len(32)]
+    pub name: String,
+    pub parent: Option<Pubkey>,
+    #[max_len(200)]
+    pub icon_uri: String,
+    pub points_multiplier_bps: u16, // Loyalty accrual multiplier (10_000 = 1x)
+    pub is_active: bool,            // Retired categories take no new coupons
+    pub coupon_count: u32,
+    pub purchase_count: u32,
+    pub redemption_count: u32,
+    pub created_at: i64,
+    pub bump: u8,
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct PendingConfigChange {
+    pub id: u64,
+    pub change: ConfigChange,
+    pub queued_at: i64,
+    pub eta: i64, // Earliest execution time
+    pub bump: u8,
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct Merchant {
+    pub authority: Pubkey,
+    pub merchant_id: Pubkey, // PDA seed; the creating authority, never changes
+    pub pending_authority: Option<Pubkey>, // Nominated by propose_authority_transfer
+    #[max_len(100)]
+    pub name: String,
+    #[max_len(500)]
+    pub description: String,
+    #[max_len(200)]
+    pub logo_uri: String,
+    #[max_len(200)]
+    pub website: String,
+    pub total_coupons_created: u32,
+    pub total_campaigns: u32, // Next campaign id (PDA seed)
+    pub total_redemptions: u32,
+    pub total_revenue: u64,
+    pub rating_sum: u64,
+    pub rating_count: u32,
+    pub is_verified: bool,
+    pub verification_evidence: [u8; 32], // Hash of KYC/KYB evidence for the last decision
+    pub verified_by: Pubkey,
+    pub verification_updated_at: i64,
+    pub is_paused: bool,             // Emergency pause
+    pub is_closing: bool,            // Wind-down started; no new sales or coupons
+    pub open_coupons: u32,           // Coupons not yet closed; must be 0 to close
+    pub is_closed: bool,             // Tombstone left by close_merchant
+    pub tier_discount_bps: [u16; 4], // Indexed by LoyaltyTier
+    #[max_len(5)]
+    pub staff: Vec<Pubkey>, // May reply to reviews on the merchant's behalf
+    pub created_at: i64,
+    pub bump: u8,
+}
+
+impl Merchant {
+    /// Account size (with discriminator) holding strings at their actual length
+    pub fn space(name: &str, description: &str, logo_uri: &str, website: &str) -> usize {
+        8 + Merchant::INIT_SPACE
+            - (MAX_MERCHANT_NAME_LEN + MAX_DESCRIPTION_LEN + MAX_URI_LEN + MAX_WEBSITE_LEN)
+            + name.len()
+            + description.len()
+            + logo_uri.len()
+            + website.len()
+    }
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct Coupon {
+    pub mint: Pubkey,
+    pub merchant: Pubkey,
+    pub campaign: Option<Pubkey>, // Budget and counters are tracked there when set
+    pub index: u32,               // Position in the merchant's catalogue
+    pub discount_percent: u8,
+    pub discount_fixed: u64,
+    pub price: u64,
+    pub payment_mint: Pubkey, // Pinned at creation; sales and refunds use it
+    pub expiry_timestamp: i64,
+    pub max_redemptions: u32,
+    pub current_redemptions: u32,
+    pub category: Pubkey,
+    pub is_transferable: bool,
+    pub is_active: bool,
+    #[max_len(200)]
+    pub metadata_uri: String,
+    pub created_at: i64,
+    pub total_purchases: u32,
+    pub paid_total: u64,     // What buyers actually paid, after every discount
+    pub holder_reviews: u32, // Reviews backed by a held unit (at most one per unredeemed unit)
+    pub gate: CouponGate,
+    pub max_points_per_purchase: u32,
+    pub rating_sum: u64,
+    pub rating_count: u32,
+    pub rating_histogram: [u32; 5], // Review count by star, 1 to 5
+    pub is_closed: bool,            // Tombstone: kept so reviews and redemptions resolve
+    pub refund_per_unit: u64,       // Average paid per unit sold (set on close)
+    pub refunds_remaining: u32,     // Outstanding units not yet refunded
+    pub bump: u8,
+}
+
+impl Coupon {
+    /// Size of a closed coupon: metadata URI cleared, everything else kept
+    pub const TOMBSTONE_SPACE: usize = 8 + Coupon::INIT_SPACE - MAX_URI_LEN;
+}
+
+/// Maps a merchant's catalogue position to its coupon so clients can page by index
+#[account]
+#[derive(InitSpace)]
+pub struct CouponIndexEntry {
+    pub merchant: Pubkey,
+    pub index: u32,
+    pub coupon: Pubkey,
+    pub bump: u8,
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct Campaign {
+    pub merchant: Pubkey,
+    pub id: u32, // Index under the merchant (PDA seed)
+    #[max_len(64)]
+    pub name: String,
+    pub start_timestamp: i64,
+    pub end_timestamp: i64,
+    pub budget: u64,           // Total discount the campaign may issue
+    pub budget_committed: u64, // Worst-case coupon discounts plus sale-time discounts
+    pub total_coupons: u32,
+    pub total_purchases: u32,
+    pub total_redemptions: u32,
+    pub total_revenue: u64,
+    pub created_at: i64,
+    pub bump: u8,
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct Review {
+    pub coupon: Pubkey,
+    pub user: Pubkey,
+    pub rating: u8,
+    #[max_len(500)]
+    pub comment: String,
+    pub timestamp: i64,
+    pub updated_at: i64,
+    pub edit_count: u16,
+    pub history_hash: [u8; 32], // Chained hash of all previous versions
+    pub is_hidden: bool,        // Hidden by moderation; excluded from ratings
+    pub flag_count: u32,
+    pub counted_for_achievements: bool, // Added to the author's review count
+    pub has_reply: bool,                // Merchant reply exists; closed with the review
+    pub proof: ReviewProof,
+    pub bump: u8,
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct LoyaltyBadge {
+    pub user: Pubkey,
+    pub tier: LoyaltyTier,
+    pub deals_purchased: u32,
+    pub total_saved: u64,
+    pub points: u32,      // Spendable balance
+    pub tier_points: u32, // Earned total that determines the tier
+    pub created_at: i64,
+    pub last_decay_at: i64,    // Start of the current decay period
+    pub badge_mint: Pubkey,    // Soulbound Token-2022 NFT
+    pub nft_tier: LoyaltyTier, // Tier currently shown in the NFT metadata
+    pub nft_uri_version: u32,  // Config URI base version the NFT metadata points at
+    pub bump: u8,
+}
+
+/// Merchant stamp-card program: every `stamps_required` redemptions earn a reward
+#[account]
+#[derive(InitSpace)]
+pub struct MerchantLoyaltyProgram {
+    pub merchant: Pubkey,
+    pub reward_coupon: Pubkey, // Coupon minted as the reward
+    pub stamps_required: u16,
+    pub is_active: bool,
+    pub rewards_issued: u32,
+    pub created_at: i64,
+    pub bump: u8,
+}
+
+/// Per-(merchant, customer) stamp progress
+#[account]
+#[derive(InitSpace)]
+pub struct StampCard {
+    pub merchant: Pubkey,
+    pub customer: Pubkey,
+    pub stamps: u16,
+    pub rewards_available: u16, // Earned but not yet claimed
+    pub rewards_claimed: u32,
+    pub bump: u8,
+}
+
+/// Admin-defined goal that awards bonus points once reached
+#[account]
+#[derive(InitSpace)]
+pub struct AchievementDefinition {
+    pub id: u16, // Bit position in `Achievements.claimed`
+    pub kind: AchievementKind,
+    pub target: u32,
+    pub reward_points: u32,
+    pub is_active: bool,
+    pub bump: u8,
+}
+
+/// Per-user achievement progress
+#[account]
+#[derive(InitSpace)]
+pub struct Achievements {
+    pub user: Pubkey,
+    pub redemptions: u32,
+    pub reviews: u32,
+    #[max_len(16)]
+    pub categories_redeemed: Vec<u32>, // Distinct category ids, capped
+    pub claimed: u64, // Bitmask by achievement id
+    pub bump: u8,
+}
+
+/// A user's report against a review (one per flagger and review)
+#[account]
+#[derive(InitSpace)]
+pub struct ReviewFlag {
+    pub review: Pubkey,
+    pub flagger: Pubkey,
+    pub reason: FlagReason,
+    pub timestamp: i64,
+    pub bump: u8,
+}
+
+/// Per-user flag rate limit
+#[account]
+#[derive(InitSpace)]
+pub struct FlaggerState {
+    pub flagger: Pubkey,
+    pub window_start: i64,
+    pub flags_in_window: u16,
+    pub total_flags: u32,
+    pub bump: u8,
+}
+
+/// Merchant's public answer to a review (one per review)
+#[account]
+#[derive(InitSpace)]
+pub struct ReviewReply {
+    pub review: Pubkey,
+    pub merchant: Pubkey,
+    pub author: Pubkey, // Merchant authority or staff member
+    #[max_len(500)]
+    pub comment: String,
+    pub timestamp: i64,
+    pub bump: u8,
+}
+
+/// CRITICAL: Prevents duplicate redemptions
+/// One PDA per user/coupon pair ensures uniqueness
+#[account]
+#[derive(InitSpace)]
+pub struct RedemptionRecord {
+    pub coupon: Pubkey,
+    pub user: Pubkey,
+    pub timestamp: i64,
+    pub bump: u8,
+}
 
-    pub mint: Account<'info, Mint>,
+// ============ ENUMS ============
 
-    pub token_program: Program<'info, Token>,
-    pub associated_token_program: Program<'info, AssociatedToken>,
-    pub system_program: Program<'info, System>,
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq, PartialOrd, Ord, InitSpace)]
+pub enum LoyaltyTier {
+    Bronze,
+    Silver,
+    Gold,
+    Platinum,
 }
 
-#[derive(Accounts)]
-pub struct RedeemCoupon<'info> {
-    #[account(
-        mut,
-        seeds = [b"coupon", coupon.mint.as_ref()],
-        bump = coupon.bump
-    )]
-    pub coupon: Account<'info, Coupon>,
+/// What an achievement counts
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq, InitSpace)]
+pub enum AchievementKind {
+    RedemptionCount,
+    ReviewCount,
+    CategoriesRedeemed, // Distinct categories
+}
 
-    #[account(
-        mut,
-        seeds = [b"merchant", merchant.authority.as_ref()],
-        bump = merchant.bump
-    )]
-    pub merchant: Account<'info, Merchant>,
+/// Why a review was flagged
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq, InitSpace)]
+pub enum FlagReason {
+    Spam,
+    Offensive,
+    Misleading,
+    Other,
+}
 
-    #[account(mut)]
-    pub user: Signer<'info>,
+/// What entitled a user to review a coupon
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq, InitSpace)]
+pub enum ReviewProof {
+    HeldUnit,   // Held an unredeemed unit; uses one of the coupon's holder review slots
+    Redemption, // Redeemed the coupon; bound to the user by the redemption record
+}
 
-    #[account(
-        mut,
-        constraint = user_nft_account.mint == coupon.mint @ ErrorCode::WrongNFT,
-        constraint = user_nft_account.owner == user.key() @ ErrorCode::UserDoesNotOwnNFT,
-        constraint = user_nft_account.amount == 1 @ ErrorCode::InvalidNFTAmount
-    )]
-    pub user_nft_account: Account<'info, TokenAccount>,
+/// Who pays for the discount when a buyer spends loyalty points.
+///
+/// `Treasury` invites self-dealing: a merchant can buy from itself through a
+/// second wallet, earn points and spend them for a subsidy. Merchants can't
+/// earn or spend points on their own coupons from the authority wallet, and
+/// only verified merchants are subsidized.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq, InitSpace)]
+pub enum PointsFunding {
+    Merchant, // Merchant receives less
+    Treasury, // Platform treasury tops up verified merchants only
+}
 
-    /// CRITICAL: Prevents duplicate redemptions (PDA ensures uniqueness)
-    #[account(
-        init,
-        payer = user,
-        space = 8 + RedemptionRecord::INIT_SPACE,
-        seeds = [b"redemption", coupon.key().as_ref(), user.key().as_ref()],
-        bump
-    )]
-    pub redemption_record: Account<'info, RedemptionRecord>,
+impl LoyaltyTier {
+    /// Position of the tier in per-tier tables
+    pub fn index(&self) -> usize {
+        match self {
+            LoyaltyTier::Bronze => 0,
+            LoyaltyTier::Silver => 1,
+            LoyaltyTier::Gold => 2,
+            LoyaltyTier::Platinum => 3,
+        }
+    }
 
-    pub mint: Account<'info, Mint>,
-    pub token_program: Program<'info, Token>,
-    pub system_program: Program<'info, System>,
+    /// Display name used in badge NFT metadata
+    pub fn name(&self) -> &'static str {
+        match self {
+            LoyaltyTier::Bronze => "Bronze",
+            LoyaltyTier::Silver => "Silver",
+            LoyaltyTier::Gold => "Gold",
+            LoyaltyTier::Platinum => "Platinum",
+        }
+    }
 }
 
-#[derive(Accounts)]
-pub struct SubmitReview<'info> {
-    #[account(
-        init,
-        payer = user,
-        space = 8 + Review::INIT_SPACE,
-        seeds = [b"review", coupon.key().as_ref(), user.key().as_ref()],
-        bump
-    )]
-    pub review: Account<'info, Review>,
+// ============ STRUCTS ============
 
-    #[account(
-        seeds = [b"coupon", coupon.mint.as_ref()],
-        bump = coupon.bump
-    )]
-    pub coupon: Account<'info, Coupon>,
+/// Purchase restrictions for a coupon (all set rules must pass)
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq, InitSpace)]
+pub struct CouponGate {
+    /// Minimum loyalty tier the buyer's badge must hold
+    pub min_tier: Option<LoyaltyTier>,
+    /// Mint the buyer must hold a balance of (e.g. a membership token)
+    pub required_mint: Option<Pubkey>,
+    /// Minimum balance of `required_mint` (0 when no mint is required)
+    pub required_amount: u64,
+    /// Verified Metaplex collection the buyer must hold an NFT from
+    pub required_collection: Option<Pubkey>,
+}
 
-    #[account(
-        mut,
-        seeds = [b"merchant", merchant.authority.as_ref()],
-        bump = merchant.bump
-    )]
-    pub merchant: Account<'info, Merchant>,
+/// Fee and limit change applied through the config timelock
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq, InitSpace)]
+pub enum ConfigChange {
+    PointsConfig {
+        point_value: u64,
+        points_funding: PointsFunding,
+    },
+    LoyaltyRules(LoyaltyRules),
+    UnverifiedMaxPrice(u64),
+    CategoryMultiplier {
+        category: Pubkey,
+        multiplier_bps: u16,
+    },
+}
 
-    /// User's NFT account (may be empty if redeemed)
-    #[account(
-        constraint = user_nft_account.mint == coupon.mint @ ErrorCode::WrongNFT,
-        constraint = user_nft_account.owner == user.key()
-    )]
-    pub user_nft_account: Account<'info, TokenAccount>,
+/// Protocol-wide circuit breakers, one per instruction class. Admin and
+/// verifier instructions and the per-merchant pause are never blocked.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Default, PartialEq, Eq, InitSpace)]
+pub struct PauseFlags {
+    pub purchases: bool,
+    pub redemptions: bool, // Includes refund claims
+    pub reviews: bool,
+    pub merchant_operations: bool,
+    pub loyalty: bool,
+}
 
-    /// Redemption record (proves user purchased/redeemed)
-    #[account(
-        seeds = [b"redemption", coupon.key().as_ref(), user.key().as_ref()],
-        bump = redemption_record.bump
-    )]
-    pub redemption_record: Account<'info, RedemptionRecord>,
+/// Fields to change in `update_coupon` (`None` leaves a field unchanged)
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct CouponUpdate {
+    pub price: Option<u64>,
+    pub metadata_uri: Option<String>,
+    pub category: Option<Pubkey>, // Pass the category as `new_category`
+    pub max_redemptions: Option<u32>,
+    pub expiry_timestamp: Option<i64>,
+    pub discount_percent: Option<u8>,
+    pub discount_fixed: Option<u64>,
+}
 
-    #[account(mut)]
-    pub user: Signer<'info>,
+/// One field changed by `update_coupon`, with its previous and new value
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub enum CouponChange {
+    Price { old: u64, new: u64 },
+    MetadataUri { old: String, new: String },
+    Category { old: Pubkey, new: Pubkey },
+    MaxRedemptions { old: u32, new: u32 },
+    ExpiryTimestamp { old: i64, new: i64 },
+    DiscountPercent { old: u8, new: u8 },
+    DiscountFixed { old: u64, new: u64 },
+}
 
-    pub system_program: Program<'info, System>,
+/// Tier thresholds and point accrual, stored in `GlobalConfig`
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq, InitSpace)]
+pub struct LoyaltyRules {
+    /// Minimum tier points for Silver, Gold and Platinum
+    pub tier_thresholds: [u32; 3],
+    /// Points earned per whole payment token spent
+    pub points_per_token: u32,
+    /// Seconds between decay steps (0 disables decay)
+    pub decay_period: i64,
+    /// Share of points lost per period (10_000 = all points lapse)
+    pub decay_bps: u16,
 }
 
-#[derive(Accounts)]
-pub struct UpdateCouponStatus<'info> {
-    #[account(
-        mut,
-        seeds = [b"coupon", coupon.mint.as_ref()],
-        bump = coupon.bump,
-        constraint = coupon.merchant == merchant.key() @ ErrorCode::Unauthorized
-    )]
-    pub coupon: Account<'info, Coupon>,
+impl Default for LoyaltyRules {
+    fn default() -> Self {
+        Self {
+            tier_thresholds: [100, 500, 1_000],
+            points_per_token: 1,
+            decay_period: 0,
+            decay_bps: 0,
+        }
+    }
+}
 
-    #[account(
-        seeds = [b"merchant", merchant.authority.as_ref()],
-        bump = merchant.bump,
-        has_one = authority @ ErrorCode::Unauthorized
-    )]
-    pub merchant: Account<'info, Merchant>,
+// ============ EVENTS ============
 
-    pub authority: Signer<'info>,
+#[event]
+pub struct ConfigInitialized {
+    pub config: Pubkey,
+    pub admin: Pubkey,
+    pub timestamp: i64,
 }
 
-#[derive(Accounts)]
-pub struct ToggleMerchantPause<'info> {
-    #[account(
-        mut,
-        seeds = [b"merchant", merchant.authority.as_ref()],
-        bump = merchant.bump,
-        has_one = authority @ ErrorCode::Unauthorized
-    )]
-    pub merchant: Account<'info, Merchant>,
-
-    pub authority: Signer<'info>,
+#[event]
+pub struct CategoryCreated {
+    pub category: Pubkey,
+    pub id: u32,
+    pub name: String,
+    pub parent: Option<Pubkey>,
+    pub points_multiplier_bps: u16,
+    pub timestamp: i64,
 }
 
-#[derive(Accounts)]
-pub struct InitializeLoyaltyBadge<'info> {
-    #[account(
-        init,
-        payer = user,
-        space = 8 + LoyaltyBadge::INIT_SPACE,
-        seeds = [b"loyalty", user.key().as_ref()],
-        bump
-    )]
-    pub loyalty_badge: Account<'info, LoyaltyBadge>,
+#[event]
+pub struct CategoryUpdated {
+    pub category: Pubkey,
+    pub name: String,
+    pub icon_uri: String,
+    pub is_active: bool,
+    pub timestamp: i64,
+}
 
-    #[account(mut)]
-    pub user: Signer<'info>,
+#[event]
+pub struct CategoryMultiplierUpdated {
+    pub category: Pubkey,
+    pub multiplier_bps: u16,
+    pub timestamp: i64,
+}
 
-    pub system_program: Program<'info, System>,
+#[event]
+pub struct ConfigChangeQueued {
+    pub id: u64,
+    pub change: ConfigChange,
+    pub eta: i64,
+    pub timestamp: i64,
 }
 
-#[derive(Accounts)]
-pub struct UpdateLoyaltyBadge<'info> {
-    #[account(
-        mut,
-        seeds = [b"loyalty", user.key().as_ref()],
-        bump = loyalty_badge.bump,
-        constraint = loyalty_badge.user == user.key() @ ErrorCode::Unauthorized
-    )]
-    pub loyalty_badge: Account<'info, LoyaltyBadge>,
+#[event]
+pub struct ConfigChangeExecuted {
+    pub id: u64,
+    pub timestamp: i64,
+}
 
-    pub user: Signer<'info>,
+#[event]
+pub struct ConfigChangeCancelled {
+    pub id: u64,
+    pub timestamp: i64,
 }
 
-#[derive(Accounts)]
-pub struct CloseExpiredCoupon<'info> {
-    #[account(
-        mut,
-        close = merchant_authority,
-        seeds = [b"coupon", coupon.mint.as_ref()],
-        bump = coupon.bump,
-        constraint = coupon.merchant == merchant.key() @ ErrorCode::Unauthorized
-    )]
-    pub coupon: Account<'info, Coupon>,
+#[event]
+pub struct PointsConfigUpdated {
+    pub point_value: u64,
+    pub points_funding: PointsFunding,
+    pub timestamp: i64,
+}
 
-    #[account(
-        seeds = [b"merchant", merchant_authority.key().as_ref()],
-        bump = merchant.bump
-    )]
-    pub merchant: Account<'info, Merchant>,
+#[event]
+pub struct LoyaltyRulesUpdated {
+    pub rules: LoyaltyRules,
+    pub timestamp: i64,
+}
 
-    #[account(mut)]
-    pub merchant_authority: Signer<'info>,
+#[event]
+pub struct MerchantInitialized {
+    pub merchant: Pubkey,
+    pub authority: Pubkey,
+    pub timestamp: i64,
 }
 
-// ============ STATE ACCOUNTS ============
+#[event]
+pub struct MerchantUpdated {
+    pub merchant: Pubkey,
+    pub name: String,
+    pub description: String,
+    pub logo_uri: String,
+    pub website: String,
+    pub timestamp: i64,
+}
 
-#[account]
-#[derive(InitSpace)]
-pub struct Merchant {
+#[event]
+pub struct MerchantAuthorityTransferProposed {
+    pub merchant: Pubkey,
     pub authority: Pubkey,
-    #[max_len(100)]
+    pub pending_authority: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct MerchantAuthorityTransferCancelled {
+    pub merchant: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct MerchantAuthorityTransferred {
+    pub merchant: Pubkey,
+    pub previous_authority: Pubkey,
+    pub new_authority: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct CampaignCreated {
+    pub campaign: Pubkey,
+    pub merchant: Pubkey,
+    pub id: u32,
     pub name: String,
-    #[max_len(500)]
-    pub description: String,
-    pub total_coupons_created: u32,
-    pub total_redemptions: u32,
-    pub total_revenue: u64,
-    pub rating_sum: u64,
-    pub rating_count: u32,
-    pub is_verified: bool,
-    pub is_paused: bool, // Emergency pause
-    pub created_at: i64,
-    pub bump: u8,
+    pub start_timestamp: i64,
+    pub end_timestamp: i64,
+    pub budget: u64,
+    pub timestamp: i64,
 }
 
-#[account]
-#[derive(InitSpace)]
-pub struct Coupon {
+#[event]
+pub struct CouponCreated {
+    pub coupon: Pubkey,
     pub mint: Pubkey,
     pub merchant: Pubkey,
+    pub campaign: Option<Pubkey>,
+    pub index: u32,
     pub discount_percent: u8,
-    pub discount_fixed: u64,
     pub price: u64,
-    pub expiry_timestamp: i64,
     pub max_redemptions: u32,
-    pub current_redemptions: u32,
-    pub category: CouponCategory,
-    pub is_transferable: bool,
-    pub is_active: bool,
-    #[max_len(200)]
-    pub metadata_uri: String,
-    pub created_at: i64,
-    pub total_purchases: u32,
-    pub bump: u8,
+    pub category: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct CouponPurchased {
+    pub coupon: Pubkey,
+    pub buyer: Pubkey,
+    pub list_price: u64,
+    pub price: u64, // Amount actually charged
+    pub tier: Option<LoyaltyTier>,
+    pub tier_discount: u64,
+    pub points_spent: u32,
+    pub points_discount: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct CouponRedeemed {
+    pub coupon: Pubkey,
+    pub user: Pubkey,
+    pub merchant: Pubkey,
+    pub timestamp: i64,
 }
 
-#[account]
-#[derive(InitSpace)]
-pub struct Review {
+#[event]
+pub struct ReviewSubmitted {
+    pub review: Pubkey,
     pub coupon: Pubkey,
     pub user: Pubkey,
     pub rating: u8,
-    #[max_len(500)]
-    pub comment: String,
     pub timestamp: i64,
-    pub bump: u8,
 }
 
-#[account]
-#[derive(InitSpace)]
-pub struct LoyaltyBadge {
+#[event]
+pub struct ReviewEdited {
+    pub review: Pubkey,
+    pub coupon: Pubkey,
     pub user: Pubkey,
-    pub tier: LoyaltyTier,
-    pub deals_purchased: u32,
-    pub total_saved: u64,
-    pub points: u32,
-    pub created_at: i64,
-    pub bump: u8,
+    pub old_rating: u8,
+    pub rating: u8,
+    pub edit_count: u16,
+    pub history_hash: [u8; 32],
+    pub timestamp: i64,
 }
 
-/// CRITICAL: Prevents duplicate redemptions
-/// One PDA per user/coupon pair ensures uniqueness
-#[account]
-#[derive(InitSpace)]
-pub struct RedemptionRecord {
+#[event]
+pub struct ReviewDeleted {
+    pub review: Pubkey,
     pub coupon: Pubkey,
     pub user: Pubkey,
+    pub rating: u8,
     pub timestamp: i64,
-    pub bump: u8,
 }
 
-// ============ ENUMS ============
-
-#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq, InitSpace)]
-pub enum CouponCategory {
-    Travel,
-    Food,
-    Shopping,
-    Entertainment,
-    Services,
-    Health,
-    Education,
-    Other,
+#[event]
+pub struct ReviewFlagged {
+    pub review: Pubkey,
+    pub flagger: Pubkey,
+    pub reason: FlagReason,
+    pub flag_count: u32,
+    pub timestamp: i64,
 }
 
-#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq, InitSpace)]
-pub enum LoyaltyTier {
-    Bronze,
-    Silver,
-    Gold,
-    Platinum,
+#[event]
+pub struct ReviewModerated {
+    pub review: Pubkey,
+    pub moderator: Pubkey,
+    pub is_hidden: bool,
+    pub reason_hash: [u8; 32],
+    pub timestamp: i64,
 }
 
-// ============ EVENTS ============
-
 #[event]
-pub struct MerchantInitialized {
+pub struct ReviewReplied {
+    pub reply: Pubkey,
+    pub review: Pubkey,
     pub merchant: Pubkey,
-    pub authority: Pubkey,
+    pub author: Pubkey,
     pub timestamp: i64,
 }
 
 #[event]
-pub struct CouponCreated {
+pub struct CouponUpdated {
     pub coupon: Pubkey,
-    pub mint: Pubkey,
-    pub merchant: Pubkey,
-    pub discount_percent: u8,
-    pub price: u64,
-    pub max_redemptions: u32,
-    pub category: CouponCategory,
+    pub changes: Vec<CouponChange>,
     pub timestamp: i64,
 }
 
 #[event]
-pub struct CouponPurchased {
+pub struct CouponRestocked {
     pub coupon: Pubkey,
-    pub buyer: Pubkey,
-    pub price: u64,
+    pub previous_max_redemptions: u32,
+    pub max_redemptions: u32,
     pub timestamp: i64,
 }
 
 #[event]
-pub struct CouponRedeemed {
+pub struct CouponClosed {
     pub coupon: Pubkey,
-    pub user: Pubkey,
     pub merchant: Pubkey,
+    pub outstanding: u32,
+    pub refund_per_unit: u64,
     pub timestamp: i64,
 }
 
 #[event]
-pub struct ReviewSubmitted {
-    pub review: Pubkey,
+pub struct RefundClaimed {
     pub coupon: Pubkey,
-    pub user: Pubkey,
-    pub rating: u8,
+    pub holder: Pubkey,
+    pub amount: u64,
+    pub refunds_remaining: u32,
     pub timestamp: i64,
 }
 
@@ -893,6 +5360,30 @@ pub struct MerchantPauseToggled {
     pub timestamp: i64,
 }
 
+#[event]
+pub struct MerchantWindDownStarted {
+    pub merchant: Pubkey,
+    pub open_coupons: u32,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct MerchantClosed {
+    pub merchant: Pubkey,
+    pub authority: Pubkey,
+    pub total_coupons_created: u32,
+    pub total_redemptions: u32,
+    pub total_revenue: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct TierDiscountsUpdated {
+    pub merchant: Pubkey,
+    pub discounts_bps: [u16; 4],
+    pub timestamp: i64,
+}
+
 #[event]
 pub struct LoyaltyBadgeCreated {
     pub badge: Pubkey,
@@ -908,6 +5399,129 @@ pub struct LoyaltyBadgeUpdated {
     pub points: u32,
 }
 
+#[event]
+pub struct LoyaltyProgramUpdated {
+    pub loyalty_program: Pubkey,
+    pub merchant: Pubkey,
+    pub reward_coupon: Pubkey,
+    pub stamps_required: u16,
+    pub is_active: bool,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct StampAdded {
+    pub stamp_card: Pubkey,
+    pub merchant: Pubkey,
+    pub customer: Pubkey,
+    pub stamps: u16,
+    pub rewards_available: u16,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct StampRewardClaimed {
+    pub stamp_card: Pubkey,
+    pub merchant: Pubkey,
+    pub customer: Pubkey,
+    pub reward_coupon: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct LoyaltyBadgeNftSynced {
+    pub badge: Pubkey,
+    pub badge_mint: Pubkey,
+    pub tier: LoyaltyTier,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct MerchantStaffUpdated {
+    pub merchant: Pubkey,
+    pub staff: Pubkey,
+    pub is_staff: bool,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct ModeratorUpdated {
+    pub moderator: Pubkey,
+    pub is_moderator: bool,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct VerifierUpdated {
+    pub verifier: Pubkey,
+    pub is_verifier: bool,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct PauseFlagsUpdated {
+    pub paused: PauseFlags,
+    pub admin: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct BadgeUriBaseUpdated {
+    pub badge_uri_base: String,
+    pub admin: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct UnverifiedMaxPriceUpdated {
+    pub unverified_max_price: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct MerchantVerificationUpdated {
+    pub merchant: Pubkey,
+    pub is_verified: bool,
+    pub evidence_hash: [u8; 32],
+    pub verifier: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct AchievementCreated {
+    pub achievement: Pubkey,
+    pub id: u16,
+    pub kind: AchievementKind,
+    pub target: u32,
+    pub reward_points: u32,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct AchievementStatusUpdated {
+    pub achievement: Pubkey,
+    pub id: u16,
+    pub is_active: bool,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct AchievementClaimed {
+    pub achievement: Pubkey,
+    pub id: u16,
+    pub user: Pubkey,
+    pub reward_points: u32,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct LoyaltyPointsDecayed {
+    pub badge: Pubkey,
+    pub user: Pubkey,
+    pub points_expired: u32,
+    pub timestamp: i64,
+}
+
 // ============ ERROR CODES ============
 
 #[error_code]
@@ -970,4 +5584,598 @@ pub enum ErrorCode {
     Unauthorized,
     #[msg("Invalid UTF-8 characters in string")]
     InvalidUtf8,
+    #[msg("Invalid gate (mint gate requires a positive amount)")]
+    InvalidGate,
+    #[msg("Loyalty badge required for this deal")]
+    LoyaltyBadgeRequired,
+    #[msg("Loyalty tier too low for this deal")]
+    LoyaltyTierTooLow,
+    #[msg("Gate token account required for this deal")]
+    GateTokenAccountRequired,
+    #[msg("Gate token account has the wrong mint")]
+    WrongGateMint,
+    #[msg("Insufficient gate token balance for this deal")]
+    InsufficientGateBalance,
+    #[msg("Invalid tier discounts (max 50%, non-decreasing by tier)")]
+    InvalidTierDiscount,
+    #[msg("Point value must be greater than zero")]
+    InvalidPointValue,
+    #[msg("Points exceed the per-purchase cap for this coupon")]
+    PointsCapExceeded,
+    #[msg("Insufficient loyalty points")]
+    InsufficientPoints,
+    #[msg("Points discount exceeds the price")]
+    PointsExceedPrice,
+    #[msg("Treasury token account required for treasury-funded points")]
+    TreasuryAccountRequired,
+    #[msg("Insufficient funds in platform treasury")]
+    InsufficientTreasuryFunds,
+    #[msg("Invalid loyalty rules (increasing thresholds, bounded rates)")]
+    InvalidLoyaltyRules,
+    #[msg("Invalid stamps required (must be between 1 and 100)")]
+    InvalidStampsRequired,
+    #[msg("Reward mint authority must be the loyalty program PDA")]
+    InvalidRewardMintAuthority,
+    #[msg("No stamp reward available to claim")]
+    NoRewardAvailable,
+    #[msg("Badge NFT already shows the current tier and URI")]
+    BadgeNftUpToDate,
+    #[msg("Invalid achievement definition")]
+    InvalidAchievement,
+    #[msg("Maximum number of achievements reached")]
+    TooManyAchievements,
+    #[msg("Achievement is not active")]
+    AchievementInactive,
+    #[msg("Achievement already claimed")]
+    AchievementAlreadyClaimed,
+    #[msg("Achievement not completed yet")]
+    AchievementNotCompleted,
+    #[msg("Invalid website (must start with https://)")]
+    InvalidWebsite,
+    #[msg("New authority must differ from the current one")]
+    InvalidNewAuthority,
+    #[msg("No pending authority transfer")]
+    NoPendingAuthority,
+    #[msg("Verifier already registered")]
+    VerifierAlreadyExists,
+    #[msg("Maximum number of verifiers reached")]
+    TooManyVerifiers,
+    #[msg("Verifier not found")]
+    VerifierNotFound,
+    #[msg("Evidence hash is required")]
+    EvidenceRequired,
+    #[msg("Price exceeds the limit for unverified merchants")]
+    PriceExceedsUnverifiedLimit,
+    #[msg("Supply cannot drop below units already sold")]
+    SupplyBelowPurchases,
+    #[msg("Expiry cannot be shortened")]
+    ExpiryShortened,
+    #[msg("Discount cannot be reduced after sales")]
+    DiscountReduced,
+    #[msg("No changes requested")]
+    NoChanges,
+    #[msg("Campaign must end after it starts and in the future")]
+    InvalidCampaignWindow,
+    #[msg("Campaign budget must be positive")]
+    InvalidCampaignBudget,
+    #[msg("Campaign budget exceeded")]
+    CampaignBudgetExceeded,
+    #[msg("Campaign has ended")]
+    CampaignEnded,
+    #[msg("Campaign is not running")]
+    CampaignNotActive,
+    #[msg("Coupon's campaign account is required")]
+    CampaignRequired,
+    #[msg("Campaign does not match the coupon")]
+    WrongCampaign,
+    #[msg("Merchant is winding down")]
+    MerchantClosing,
+    #[msg("Merchant wind-down has not started")]
+    MerchantNotClosing,
+    #[msg("Merchant still has open coupons")]
+    OpenCouponsRemain,
+    #[msg("Coupon is closed")]
+    CouponClosed,
+    #[msg("Coupon is not closed")]
+    CouponNotClosed,
+    #[msg("Refund vault and merchant token account required for outstanding units")]
+    RefundVaultRequired,
+    #[msg("No refunds left for this coupon")]
+    NoRefundAvailable,
+    #[msg("Token account mint does not match the refund mint")]
+    WrongRefundMint,
+    #[msg("Operation is paused protocol-wide")]
+    ProtocolPaused,
+    #[msg("Timelock delay is below the minimum")]
+    TimelockTooShort,
+    #[msg("Timelock has not elapsed")]
+    TimelockNotElapsed,
+    #[msg("Category is inactive")]
+    CategoryInactive,
+    #[msg("Category account is required")]
+    CategoryRequired,
+    #[msg("Category does not match")]
+    WrongCategory,
+    #[msg("Staff member already registered")]
+    StaffAlreadyExists,
+    #[msg("Maximum number of staff reached")]
+    TooManyStaff,
+    #[msg("Staff member not found")]
+    StaffNotFound,
+    #[msg("Moderator already registered")]
+    ModeratorAlreadyExists,
+    #[msg("Maximum number of moderators reached")]
+    TooManyModerators,
+    #[msg("Moderator not found")]
+    ModeratorNotFound,
+    #[msg("Cannot flag your own review")]
+    CannotFlagOwnReview,
+    #[msg("Too many flags in the current window")]
+    FlagRateLimited,
+    #[msg("Review is hidden by moderation")]
+    ReviewHidden,
+    #[msg("Collection NFT and its metadata required for this deal")]
+    CollectionNftRequired,
+    #[msg("NFT is not a verified member of the gate collection")]
+    NotInCollection,
+    #[msg("Merchant is closed")]
+    MerchantClosed,
+    #[msg("Payment mint does not match the coupon")]
+    WrongPaymentMint,
+    #[msg("Achievements account required")]
+    AchievementsRequired,
+    #[msg("Every unredeemed unit already backs a holder review")]
+    NoHolderReviewSlot,
+    #[msg("Flagged reviews cannot be deleted")]
+    ReviewFlagged,
+    #[msg("Review reply and its author must be passed")]
+    ReviewReplyRequired,
+    #[msg("Merchants cannot spend points on their own coupons")]
+    SelfPurchasePoints,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Fixture {
+        coupon: Pubkey,
+        mint: Pubkey,
+        user: Pubkey,
+    }
+
+    impl Fixture {
+        fn new() -> Self {
+            Self {
+                coupon: Pubkey::new_unique(),
+                mint: Pubkey::new_unique(),
+                user: Pubkey::new_unique(),
+            }
+        }
+
+        fn nft(&self, amount: u64) -> spl_token::state::Account {
+            spl_token::state::Account {
+                mint: self.mint,
+                owner: self.user,
+                amount,
+                ..Default::default()
+            }
+        }
+
+        fn record(&self) -> RedemptionRecord {
+            RedemptionRecord {
+                coupon: self.coupon,
+                user: self.user,
+                timestamp: 0,
+                bump: 255,
+            }
+        }
+
+        fn proof(
+            &self,
+            nft: Option<&spl_token::state::Account>,
+            record: Option<&RedemptionRecord>,
+        ) -> Option<ReviewProof> {
+            review_proof(&self.coupon, &self.mint, &self.user, nft, record)
+        }
+    }
+
+    #[test]
+    fn holder_can_review_without_redemption_record() {
+        let f = Fixture::new();
+        assert!(f.proof(Some(&f.nft(1)), None) == Some(ReviewProof::HeldUnit));
+    }
+
+    #[test]
+    fn redeemer_can_review_without_nft_account() {
+        let f = Fixture::new();
+        assert!(f.proof(None, Some(&f.record())) == Some(ReviewProof::Redemption));
+    }
+
+    #[test]
+    fn redeemer_with_burned_nft_can_review() {
+        let f = Fixture::new();
+        assert!(f.proof(Some(&f.nft(0)), Some(&f.record())) == Some(ReviewProof::Redemption));
+    }
+
+    #[test]
+    fn redemption_record_is_preferred_over_a_held_unit() {
+        // A redeemer who still holds another unit must not use up a holder slot
+        let f = Fixture::new();
+        assert!(f.proof(Some(&f.nft(1)), Some(&f.record())) == Some(ReviewProof::Redemption));
+    }
+
+    #[test]
+    fn no_proof_is_rejected() {
+        let f = Fixture::new();
+        assert!(f.proof(None, None).is_none());
+    }
+
+    #[test]
+    fn empty_nft_account_alone_is_rejected() {
+        let f = Fixture::new();
+        assert!(f.proof(Some(&f.nft(0)), None).is_none());
+    }
+
+    #[test]
+    fn nft_of_another_mint_or_owner_is_rejected() {
+        let f = Fixture::new();
+        let mut other_mint = f.nft(1);
+        other_mint.mint = Pubkey::new_unique();
+        assert!(f.proof(Some(&other_mint), None).is_none());
+
+        let mut other_owner = f.nft(1);
+        other_owner.owner = Pubkey::new_unique();
+        assert!(f.proof(Some(&other_owner), None).is_none());
+    }
+
+    #[test]
+    fn redemption_record_of_another_user_or_coupon_is_rejected() {
+        let f = Fixture::new();
+        let mut other_user = f.record();
+        other_user.user = Pubkey::new_unique();
+        assert!(f.proof(None, Some(&other_user)).is_none());
+
+        let mut other_coupon = f.record();
+        other_coupon.coupon = Pubkey::new_unique();
+        assert!(f.proof(None, Some(&other_coupon)).is_none());
+    }
+
+    #[test]
+    fn passed_on_unit_cannot_back_a_second_review() {
+        // One unit sold: the first holder reviews, then hands the NFT on
+        assert!(has_holder_review_slot(0, 1, 0));
+        assert!(!has_holder_review_slot(1, 1, 0));
+        // A second unit in circulation backs one more
+        assert!(has_holder_review_slot(1, 2, 0));
+    }
+
+    #[test]
+    fn redeemed_units_back_no_holder_reviews() {
+        assert!(!has_holder_review_slot(0, 1, 1));
+        assert!(has_holder_review_slot(1, 3, 1));
+    }
+
+    #[test]
+    fn redeeming_reviewer_frees_holder_slot() {
+        // Units sold to A and B; A reviews as a holder, then redeems
+        let mut proof = ReviewProof::HeldUnit;
+        let mut holder_reviews = 1;
+        release_holder_review(&mut proof, &mut holder_reviews);
+        assert!(proof == ReviewProof::Redemption);
+        assert!(has_holder_review_slot(holder_reviews, 2, 1));
+
+        // A review already resting on a redemption is left alone
+        release_holder_review(&mut proof, &mut holder_reviews);
+        assert_eq!(holder_reviews, 0);
+    }
+
+    /// Borsh layout of a Metaplex metadata account up to its collection
+    fn metadata(mint: &Pubkey, collection: Option<(bool, Pubkey)>) -> Vec<u8> {
+        let mut data = vec![METADATA_V1_KEY];
+        Pubkey::new_unique().serialize(&mut data).unwrap();
+        mint.serialize(&mut data).unwrap();
+        // Null-padded name, symbol and uri as stored on-chain
+        for len in [32usize, 10, 200] {
+            let mut field = b"x".to_vec();
+            field.resize(len, 0);
+            field.serialize(&mut data).unwrap();
+        }
+        500u16.serialize(&mut data).unwrap();
+        Some(vec![(Pubkey::new_unique(), true, 100u8)])
+            .serialize(&mut data)
+            .unwrap();
+        (true, true, Some(255u8), Some(0u8))
+            .serialize(&mut data)
+            .unwrap();
+        collection.serialize(&mut data).unwrap();
+        // Trailing fields (uses, collection details, ...) are ignored
+        data.extend_from_slice(&[0; 16]);
+        data
+    }
+
+    #[test]
+    fn verified_collection_is_read_from_metadata() {
+        let mint = Pubkey::new_unique();
+        let collection = Pubkey::new_unique();
+        let data = metadata(&mint, Some((true, collection)));
+        assert_eq!(verified_collection(&data, &mint), Some(collection));
+    }
+
+    #[test]
+    fn unverified_or_missing_collection_is_rejected() {
+        let mint = Pubkey::new_unique();
+        let unverified = metadata(&mint, Some((false, Pubkey::new_unique())));
+        assert_eq!(verified_collection(&unverified, &mint), None);
+        assert_eq!(verified_collection(&metadata(&mint, None), &mint), None);
+    }
+
+    #[test]
+    fn metadata_of_another_mint_is_rejected() {
+        let data = metadata(&Pubkey::new_unique(), Some((true, Pubkey::new_unique())));
+        assert_eq!(verified_collection(&data, &Pubkey::new_unique()), None);
+    }
+
+    #[test]
+    fn truncated_or_foreign_metadata_is_rejected() {
+        let mint = Pubkey::new_unique();
+        let mut data = metadata(&mint, Some((true, Pubkey::new_unique())));
+        assert_eq!(verified_collection(&data[..100], &mint), None);
+        data[0] = 6; // Edition account
+        assert_eq!(verified_collection(&data, &mint), None);
+    }
+
+    #[test]
+    fn decay_counts_only_time_since_it_was_enabled() {
+        let day = 86_400;
+        // Badge untouched for a year, decay switched on 2.5 periods ago
+        let (from, periods) = pending_decay(0, 365 * day, 7 * day, 365 * day + 17 * day);
+        assert_eq!(from, 365 * day);
+        assert_eq!(periods, 2);
+
+        // Badge touched after decay was enabled decays from its last touch
+        let (from, periods) = pending_decay(10 * day, 3 * day, 7 * day, 16 * day);
+        assert_eq!(from, 10 * day);
+        assert_eq!(periods, 0);
+    }
+
+    #[test]
+    fn decay_applies_each_period_in_turn() {
+        // 10% per period: 1000 -> 900 -> 810 -> 729
+        assert_eq!(decayed_points(1_000, 1_000, 0).unwrap(), 1_000);
+        assert_eq!(decayed_points(1_000, 1_000, 1).unwrap(), 900);
+        assert_eq!(decayed_points(1_000, 1_000, 3).unwrap(), 729);
+    }
+
+    #[test]
+    fn decay_rounds_the_loss_down() {
+        // 15 * 10% = 1.5 points lost, rounded down to 1
+        assert_eq!(decayed_points(15, 1_000, 1).unwrap(), 14);
+        assert_eq!(decayed_points(9, 1_000, 1).unwrap(), 9);
+    }
+
+    #[test]
+    fn decay_past_max_steps_clears_points() {
+        assert_eq!(decayed_points(u32::MAX, 1, MAX_DECAY_STEPS + 1).unwrap(), 0);
+        assert_eq!(decayed_points(1_000, 10_000, 1).unwrap(), 0);
+    }
+
+    #[test]
+    fn decay_can_drop_the_tier() {
+        let thresholds = LoyaltyRules::default().tier_thresholds;
+        let tier_points = 1_000;
+        assert!(tier_for_points(tier_points, &thresholds) == LoyaltyTier::Platinum);
+
+        let decayed = decayed_points(tier_points, 1_000, 1).unwrap();
+        assert!(tier_for_points(decayed, &thresholds) == LoyaltyTier::Gold);
+
+        let decayed = decayed_points(tier_points, 5_000, 4).unwrap();
+        assert!(tier_for_points(decayed, &thresholds) == LoyaltyTier::Bronze);
+    }
+
+    #[test]
+    fn tier_thresholds_are_inclusive() {
+        let thresholds = [100, 500, 1_000];
+        assert!(tier_for_points(99, &thresholds) == LoyaltyTier::Bronze);
+        assert!(tier_for_points(100, &thresholds) == LoyaltyTier::Silver);
+        assert!(tier_for_points(500, &thresholds) == LoyaltyTier::Gold);
+        assert!(tier_for_points(1_000, &thresholds) == LoyaltyTier::Platinum);
+    }
+
+    fn campaign(budget: u64) -> Campaign {
+        Campaign {
+            merchant: Pubkey::new_unique(),
+            id: 0,
+            name: String::new(),
+            start_timestamp: 0,
+            end_timestamp: 1,
+            budget,
+            budget_committed: 0,
+            total_coupons: 0,
+            total_purchases: 0,
+            total_redemptions: 0,
+            total_revenue: 0,
+            created_at: 0,
+            bump: 255,
+        }
+    }
+
+    #[test]
+    fn commitment_covers_percent_and_fixed_discount() {
+        // 25% of 10 USDC plus 1 USDC fixed, for 4 units
+        assert_eq!(
+            committed_discount(10_000_000, 25, 1_000_000, 4).unwrap(),
+            14_000_000
+        );
+        assert_eq!(committed_discount(10_000_000, 0, 0, 4).unwrap(), 0);
+        assert!(committed_discount(MAX_PRICE, 100, u64::MAX, 1).is_err());
+    }
+
+    #[test]
+    fn sale_time_discounts_are_charged_to_the_budget() {
+        let mut c = campaign(100);
+        reserve_campaign_budget(&mut c, 0, 60).unwrap();
+        charge_campaign_budget(&mut c, 40).unwrap();
+        assert_eq!(c.budget_committed, 100);
+        assert!(charge_campaign_budget(&mut c, 1).is_err());
+        // A zero charge always fits
+        charge_campaign_budget(&mut c, 0).unwrap();
+    }
+
+    #[test]
+    fn reservation_can_shrink_but_not_overspend() {
+        let mut c = campaign(100);
+        reserve_campaign_budget(&mut c, 0, 80).unwrap();
+        assert!(reserve_campaign_budget(&mut c, 80, 101).is_err());
+        reserve_campaign_budget(&mut c, 80, 50).unwrap();
+        assert_eq!(c.budget_committed, 50);
+    }
+
+    #[test]
+    fn apply_bps_rounds_down() {
+        assert_eq!(apply_bps(999, 1).unwrap(), 0);
+        assert_eq!(apply_bps(10_001, 5_000).unwrap(), 5_000);
+        assert_eq!(apply_bps(1_000_000, 2_500).unwrap(), 250_000);
+    }
+
+    #[test]
+    fn apply_bps_handles_full_and_oversized_shares() {
+        assert_eq!(apply_bps(u64::MAX, 10_000).unwrap(), u64::MAX);
+        assert_eq!(apply_bps(0, u16::MAX).unwrap(), 0);
+        // More than 100% of the largest amount no longer fits a u64
+        assert!(apply_bps(u64::MAX, 10_001).is_err());
+    }
+
+    #[test]
+    fn points_convert_to_base_units_of_the_mint() {
+        // 150 points worth 0.01 token each = 1.5 tokens of a 6-decimal mint
+        assert_eq!(points_to_base_units(150, 10_000, 6).unwrap(), 1_500_000);
+        assert_eq!(points_to_base_units(150, 10_000, 9).unwrap(), 1_500_000_000);
+        assert_eq!(points_to_base_units(0, 10_000, 6).unwrap(), 0);
+    }
+
+    #[test]
+    fn points_conversion_rounds_down() {
+        // 1 point worth a millionth of a token is below one base unit at 0 decimals
+        assert_eq!(points_to_base_units(1, 1, 0).unwrap(), 0);
+        assert_eq!(points_to_base_units(3, 500_000, 0).unwrap(), 1);
+    }
+
+    #[test]
+    fn points_conversion_overflow_is_an_error() {
+        assert!(points_to_base_units(1, 1, u8::MAX).is_err());
+        assert!(points_to_base_units(u32::MAX, u64::MAX, 30).is_err());
+        // Fits in u128 but not in the u64 result
+        assert!(points_to_base_units(u32::MAX, u64::MAX, 6).is_err());
+    }
+
+    fn merchant(name: &str, description: &str, logo_uri: &str, website: &str) -> Merchant {
+        Merchant {
+            authority: Pubkey::new_unique(),
+            merchant_id: Pubkey::new_unique(),
+            pending_authority: Some(Pubkey::new_unique()),
+            name: name.to_string(),
+            description: description.to_string(),
+            logo_uri: logo_uri.to_string(),
+            website: website.to_string(),
+            total_coupons_created: 0,
+            total_campaigns: 0,
+            total_redemptions: 0,
+            total_revenue: 0,
+            rating_sum: 0,
+            rating_count: 0,
+            is_verified: false,
+            verification_evidence: [0; 32],
+            verified_by: Pubkey::default(),
+            verification_updated_at: 0,
+            is_paused: false,
+            is_closing: false,
+            open_coupons: 0,
+            is_closed: false,
+            tier_discount_bps: [0; 4],
+            staff: vec![Pubkey::new_unique(); MAX_MERCHANT_STAFF],
+            created_at: 0,
+            bump: 255,
+        }
+    }
+
+    #[test]
+    fn merchant_space_fits_the_serialized_profile() {
+        let profiles = [
+            ("", "", "", ""),
+            (
+                "Cafe",
+                "Coffee and cake",
+                "ipfs://logo",
+                "https://cafe.example",
+            ),
+        ];
+        for (name, description, logo_uri, website) in profiles {
+            let account = merchant(name, description, logo_uri, website);
+            assert_eq!(
+                Merchant::space(name, description, logo_uri, website),
+                8 + account.try_to_vec().unwrap().len()
+            );
+        }
+    }
+
+    #[test]
+    fn merchant_space_at_max_lengths_is_init_space() {
+        let name = "n".repeat(MAX_MERCHANT_NAME_LEN);
+        let description = "d".repeat(MAX_DESCRIPTION_LEN);
+        let logo_uri = "l".repeat(MAX_URI_LEN);
+        let website = "w".repeat(MAX_WEBSITE_LEN);
+        assert_eq!(
+            Merchant::space(&name, &description, &logo_uri, &website),
+            8 + Merchant::INIT_SPACE
+        );
+    }
+
+    #[test]
+    fn refund_is_the_average_actually_paid() {
+        // Three buyers paid 10, 8 (tier discount) and 6 (points): average 8
+        assert_eq!(average_paid_per_unit(24, 3), 8);
+        // A free reward unit dilutes the average instead of being refunded on top
+        assert_eq!(average_paid_per_unit(24, 4), 6);
+        assert_eq!(average_paid_per_unit(10, 3), 3);
+    }
+
+    #[test]
+    fn refund_of_nothing_sold_is_zero() {
+        assert_eq!(average_paid_per_unit(0, 0), 0);
+        assert_eq!(average_paid_per_unit(0, 5), 0);
+    }
+
+    fn category(coupon_count: u32) -> Category {
+        Category {
+            id: 0,
+            name: String::new(),
+            parent: None,
+            icon_uri: String::new(),
+            points_multiplier_bps: BPS_DENOMINATOR as u16,
+            is_active: true,
+            coupon_count,
+            purchase_count: 0,
+            redemption_count: 0,
+            created_at: 0,
+            bump: 255,
+        }
+    }
+
+    #[test]
+    fn moving_a_coupon_updates_both_category_counts() {
+        let (mut old, mut new) = (category(3), category(0));
+        move_category_coupon(&mut old, &mut new).unwrap();
+        assert_eq!(old.coupon_count, 2);
+        assert_eq!(new.coupon_count, 1);
+    }
+
+    #[test]
+    fn moving_out_of_an_empty_category_is_rejected() {
+        let (mut old, mut new) = (category(0), category(0));
+        assert!(move_category_coupon(&mut old, &mut new).is_err());
+
+        let (mut old, mut new) = (category(1), category(u32::MAX));
+        assert!(move_category_coupon(&mut old, &mut new).is_err());
+    }
 }